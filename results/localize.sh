@@ -0,0 +1,17 @@
+#!/bin/bash
+# compares the hit rates of the reiterated courses of ec_reiter, which is
+# reversed by hand, with those of ec_localize, which isn't but runs with
+# --localize.
+cd $(dirname ${BASH_SOURCE[0]})
+
+export EC=${EC:-../ec}
+CONTEXT=../target/release/context
+
+EC_CURRICULUM=../curriculum/ec_reiter $CONTEXT ${SEED:+--seed "$SEED"} \
+             --no-store-inputs --metrics-jsonl reiter.jsonl
+EC_CURRICULUM=../curriculum/ec_localize $CONTEXT ${SEED:+--seed "$SEED"} \
+             --no-store-inputs --metrics-jsonl localize.jsonl --localize
+
+for run in reiter localize
+do echo "$run:" $(tail -n 3 $run.jsonl | grep -o '"hit_rate":[0-9]*' | cut -d: -f2)
+done
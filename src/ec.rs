@@ -21,13 +21,15 @@ extern crate tempdir;
 
 use std::f64;
 use std::str;
-use std::collections::{HashSet, HashMap};
+use std::collections::{BTreeMap, HashSet, HashMap};
 use std::env;
+use std::error::Error;
+use std::io::Write;
 use std::rc::Rc;
 use std::path::Path;
-use std::process::Command;
+use std::time::Instant;
 
-use knowledge::Context;
+use knowledge::{Context, Mechanism};
 
 // masks used at compile-time to determine what gets logged
 // 1 -> show iteration hit-rate and failures
@@ -37,12 +39,16 @@ use knowledge::Context;
 // 8 -> show ec input
 const LOG_LEVEL: u8 = 1;
 
-const STORE_INPUTS: bool = true;
 const STORE_FILENAME_PREFIX: &str = "input_contextual";
 
 const EC_GRAMMAR_INCLUDE_PROGS: bool = false;
 const EC_ACCESS_FACTOR: f64 = 400f64;
 const EC_MAX_IN_ARTIFACT: usize = 20;
+/// Combinators is the data of an item in the knowledge network used by
+/// ec: a list of learned combinators, most probable first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Combinators(pub Vec<String>);
+
 static PRIMS_ARR: [&str; 32] = ["B",
                                 "C",
                                 "S",
@@ -83,49 +89,71 @@ mod course {
     extern crate tempdir;
 
     use regex::Regex;
+    use std::collections::BTreeMap;
     use std::env;
     use std::fs::{self, File};
-    use std::io::{Read, Write};
+    use std::io::{self, BufWriter, Read, Write};
     use std::path::Path;
     use tempdir::TempDir;
 
     use knowledge::Context;
-
-    fn curriculum_path() -> String {
+    use super::{Combinators, EcError};
+
+    /// a kind of character, counted among the features of a course.
+    type CharClass = (&'static str, fn(char) -> bool);
+    static CHAR_CLASSES: [CharClass; 5] = [("upper", |c| c.is_uppercase()),
+                                           ("lower", |c| c.is_lowercase()),
+                                           ("digit", |c| c.is_numeric()),
+                                           ("space", |c| c.is_whitespace()),
+                                           ("punct", |c| c.is_ascii_punctuation())];
+
+    /// `default_curriculum` gives the curriculum directory given by the
+    /// `$EC_CURRICULUM` environment variable, or else ./curriculum/ec.
+    pub fn default_curriculum() -> Result<String, EcError> {
         if let Ok(val) = env::var("EC_CURRICULUM") {
-            val
+            Ok(val)
         } else if Path::new("./curriculum/ec").exists() {
-            String::from("./curriculum/ec")
+            Ok(String::from("./curriculum/ec"))
         } else {
-            panic!("could not find ec curriculum")
+            Err(EcError::MissingCurriculum)
         }
     }
 
-    pub fn read_curriculum(name: String) -> String {
-        let curr_path = curriculum_path();
-        let path = Path::new(&curr_path).join(name);
-        let mut f = File::open(&path).expect("opening curriculum file");
+    pub fn read_curriculum(curriculum: &str, name: &str) -> Result<String, EcError> {
+        let path = Path::new(curriculum).join(name);
+        let mut f = File::open(&path)?;
         let mut s = String::new();
-        f.read_to_string(&mut s)
-            .expect("reading curriculum file");
-        s
+        f.read_to_string(&mut s)?;
+        Ok(s)
+    }
+
+    /// `course_path` gives the path of the course file for a particular
+    /// iteration in a curriculum directory.
+    pub fn course_path(curriculum: &str, i: u64) -> Result<String, EcError> {
+        path_string(&Path::new(curriculum).join(format!("course_{:02}.json", i)))
     }
 
-    pub fn iter_max() -> u64 {
+    /// `path_string` gives a path as a string, for passing to ec, if it is
+    /// valid unicode.
+    fn path_string(path: &Path) -> Result<String, EcError> {
+        path.to_str().map(String::from).ok_or_else(|| {
+            let msg = format!("path {} is not valid unicode", path.display());
+            EcError::Io(io::Error::new(io::ErrorKind::InvalidInput, msg))
+        })
+    }
+
+    /// `iter_max` gives the number of courses in a curriculum directory.
+    pub fn iter_max(curriculum: &str) -> Result<u64, EcError> {
         let re = Regex::new(r"^course_..\.json$").unwrap();
-        let curr_path = curriculum_path();
-        fs::read_dir(Path::new(&curr_path))
-            .expect("read curriculum dir")
-            .map(|entry| entry.expect("read curriculum dir"))
-            .filter(|dir| {
-                        let path = dir.path();
-                        let rel_path = path.strip_prefix(&curr_path).unwrap();
-                        match rel_path.to_str() {
-                            Some(filename) => re.is_match(filename),
-                            _ => false,
-                        }
-                    })
-            .count() as u64
+        let mut count = 0;
+        for entry in fs::read_dir(Path::new(curriculum))? {
+            let path = entry?.path();
+            let rel_path = path.strip_prefix(curriculum).unwrap();
+            if rel_path.to_str().is_some_and(|filename| re.is_match(filename)) {
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 
     #[derive(Serialize, Deserialize)]
@@ -152,51 +180,100 @@ mod course {
         grammar: Vec<Comb>,
     }
     impl Course {
-        /// load the course file corresponding to a particular iteration.
-        pub fn load(i: u64) -> Course {
-            let s = read_curriculum(format!("course_{:02}.json", i));
-            serde_json::from_str(&s).expect("parsing course file")
+        /// load the course file of a curriculum directory corresponding to a
+        /// particular iteration.
+        pub fn load(curriculum: &str, i: u64) -> Result<Course, EcError> {
+            let s = read_curriculum(curriculum, &format!("course_{:02}.json", i))?;
+            serde_json::from_str(&s).map_err(|err| EcError::Parse("course file", err))
+        }
+        /// read a Course from any file, such as one outside the curriculum.
+        pub fn read(path: &str) -> Result<Course, EcError> {
+            let f = File::open(path)?;
+            serde_json::from_reader(f).map_err(|err| EcError::json("course file", err))
         }
         /// merge a given Course with the grammar of combinators given in the Context.
-        pub fn merge(&mut self, ctx: &Context) {
-            let raw_items = ctx.get()
+        pub fn merge(&mut self, ctx: &Context<Combinators>) {
+            let items = ctx.get()
                 .into_iter()
                 .filter(|&(_, mech, _)| mech == "ec")
                 .map(|(_, _, d)| d);
-            for raw_item in raw_items {
-                let item: Vec<String> =
-                    serde_json::from_str(&raw_item).expect("parse combinator from context");
-                let mut grammar: Vec<Comb> = item.into_iter().map(|s| Comb { expr: s }).collect();
-                self.grammar.append(&mut grammar);
+            for item in items {
+                let grammar = item.0.iter().map(|s| Comb { expr: s.clone() });
+                self.grammar.extend(grammar);
+            }
+        }
+        /// `grammar_size` gives the number of combinators in the grammar
+        /// given to ec, including those merged from a Context.
+        pub fn grammar_size(&self) -> usize {
+            self.grammar.len()
+        }
+        /// features describes the tasks of a Course, for recognizing which
+        /// part of the knowledge network it belongs to. Words in task names
+        /// are counted, and statistics of the problems are averaged over
+        /// tasks.
+        pub fn features(&self) -> BTreeMap<String, f64> {
+            let mut features = BTreeMap::new();
+            features.insert(String::from("bias"), 1f64);
+            let n = self.tasks.len() as f64;
+            for task in &self.tasks {
+                for word in task.name.split_whitespace() {
+                    let name = format!("name:{}", word.to_lowercase());
+                    *features.entry(name).or_insert(0f64) += 1f64;
+                }
+                let problems: Vec<&Problem> = task.train.iter().chain(&task.test).collect();
+                if problems.is_empty() {
+                    continue;
+                }
+                let m = n * problems.len() as f64;
+                for p in problems {
+                    let mut stats = vec![(String::from("o_in_i"), p.i.contains(&p.o) as u8 as f64),
+                                         (String::from("len_ratio"),
+                                          (p.o.len() as f64 + 1f64) / (p.i.len() as f64 + 1f64))];
+                    for &(side, s) in &[("i", &p.i), ("o", &p.o)] {
+                        let len = s.chars().count() as f64;
+                        stats.push((format!("{}_len", side), len / 10f64));
+                        if len == 0f64 {
+                            continue;
+                        }
+                        for &(class, f) in &CHAR_CLASSES {
+                            let count = s.chars().filter(|&c| f(c)).count() as f64;
+                            stats.push((format!("{}_{}", side, class), count / len));
+                        }
+                    }
+                    for (name, x) in stats {
+                        *features.entry(name).or_insert(0f64) += x / m;
+                    }
+                }
             }
+            features
         }
         /// save a Course to a temporary file
-        pub fn save(&self, i: u64) -> (TempDir, String) {
-            let tmp_dir = TempDir::new("ec").expect("make temp dir");
+        pub fn save(&self, i: u64) -> Result<(TempDir, String), EcError> {
+            let tmp_dir = TempDir::new("ec")?;
             let path = tmp_dir.path().join(format!("ec_input_{}.json", i));
-            let mut f = File::create(&path).expect("create temp file");
-            let ser = serde_json::to_string(self).expect("serialize ec input");
-            write!(f, "{}", ser).expect("write ec input");
-            let path = String::from(path.to_str().unwrap());
-            (tmp_dir, path)
+            let path = path_string(&path)?;
+            self.save_perm(&path)?;
+            Ok((tmp_dir, path))
         }
         /// save a Course to a permanent file
-        pub fn save_perm(&self, dest: &str) {
-            let path = Path::new(dest);
-            let mut f = File::create(&path).expect("create ec_input file");
-            let ser = serde_json::to_string(self).expect("serialize ec input");
-            write!(f, "{}", ser).expect("write ec input");
+        pub fn save_perm(&self, dest: &str) -> Result<(), EcError> {
+            let mut f = BufWriter::new(File::create(dest)?);
+            serde_json::to_writer(&mut f, self).map_err(|err| EcError::json("ec input", err))?;
+            f.flush()?;
+            Ok(())
         }
     }
 }
 use self::course::{Course, read_curriculum};
-pub use self::course::iter_max;
+pub use self::course::{default_curriculum, iter_max};
 
 
 /// results is for parsing output from ec.
-mod results {
+pub mod results {
     extern crate serde_json;
 
+    use super::EcError;
+
     #[derive(Clone, Serialize, Deserialize)]
     pub struct Comb {
         pub expr: String,
@@ -224,15 +301,41 @@ mod results {
         pub hit_rate: u64,
     }
     impl Results {
-        pub fn from_string(raw: String) -> Results {
-            serde_json::from_str(&raw).expect("parse ec output")
+        pub fn from_string(raw: String) -> Result<Results, EcError> {
+            serde_json::from_str(&raw).map_err(|err| EcError::Parse("ec output", err))
         }
     }
 }
 use self::results::Results;
 
+/// error is for the ways that running ec can fail.
+mod error;
+pub use self::error::EcError;
+
+/// limits is for bounding the time and resources ec may use.
+mod limits;
+pub use self::limits::Limits;
+
+/// localize is for orienting the network to suit each course.
+mod localize;
+pub use self::localize::Localizer;
+
+/// metrics is for recording what ec did in each iteration.
+mod metrics;
+use self::metrics::{Iteration, Metrics};
+
+/// compare is for comparing contextual learning against baselines.
+mod compare;
+pub use self::compare::Compare;
 
-fn ec_bin() -> String {
+/// trials is for running the same curriculum many times.
+mod trials;
+pub use self::trials::{Summary, Trials};
+
+
+/// `ec_bin` gives the ec binary given by the `$EC` environment variable, or
+/// else ./ec if it exists, or else ec from `$PATH`.
+pub fn ec_bin() -> String {
     if let Ok(val) = env::var("EC") {
         val
     } else if Path::new("./ec").exists() {
@@ -242,17 +345,37 @@ fn ec_bin() -> String {
     }
 }
 
-/// if `STORE_INPUTS` is true, this is where the inputs are saved.
-fn store_input_path(i: u64) -> String {
-    let store_dir = env::var("EC_STORAGE").unwrap_or_else(|_| String::from("ec_storage"));
+/// `ec_one_bin` is like `ec_bin`, but for the ec binary that runs a single
+/// iteration, given by `$EC_ONE`. It is used by the primitive baseline of a
+/// comparison.
+pub fn ec_one_bin() -> String {
+    if let Ok(val) = env::var("EC_ONE") {
+        val
+    } else if Path::new("./ec_one").exists() {
+        String::from("./ec_one")
+    } else {
+        String::from("ec_one")
+    }
+}
+
+/// if an Ec stores its inputs, this is the directory they are saved in
+/// unless it is given another (see `Ec::store_inputs_in`).
+fn default_storage() -> String {
+    env::var("EC_STORAGE").unwrap_or_else(|_| String::from("ec_storage"))
+}
+
+/// if an Ec stores its inputs, this is where the input of an iteration is
+/// saved within its storage directory.
+fn store_input_path(store_dir: &str, i: u64) -> String {
     format!("{}/{}_{}.json", store_dir, STORE_FILENAME_PREFIX, i)
 }
 
-/// embryo returns the embryo (embryo.json in the curriculum/ec directory)
-/// for use by the Skn that uses ec.
-pub fn embryo() -> Vec<(&'static str, String)> {
-    let s = read_curriculum(String::from("embryo.json"));
-    vec![("ec", s)]
+/// embryo returns the embryo (embryo.json in the curriculum directory) for
+/// use by the Skn that uses ec.
+pub fn embryo(curriculum: &str) -> Result<Vec<(&'static str, Combinators)>, EcError> {
+    let s = read_curriculum(curriculum, "embryo.json")?;
+    let combs = serde_json::from_str(&s).map_err(|err| EcError::Parse("embryo file", err))?;
+    Ok(vec![("ec", combs)])
 }
 
 /// primitives returns the set of expressions that are primitive to ec.
@@ -260,57 +383,61 @@ fn primitives() -> HashSet<String> {
     PRIMS_ARR.iter().map(|&s| String::from(s)).collect()
 }
 
+/// `exec_ec` runs an ec binary on the input at path within the given
+/// Limits and parses its output.
+fn exec_ec(bin: &str, path: &str, i: u64, limits: &Limits) -> Result<Results, EcError> {
+    let output = limits.run(bin, path, i)?;
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    let raw_results = String::from_utf8_lossy(&output.stdout).into_owned();
+    if LOG_LEVEL & 4 != 0 {
+        let err = if stderr.is_empty() { String::from("") } else {
+            format!("EC ERROR:\n{}\n", stderr)
+        };
+        println!("{}EC OUTPUT:\n{}", err, raw_results)
+    }
+    Results::from_string(raw_results)
+}
+
 /// `run_ec` is the lower-level function that produces the ec results for a
-/// given context and course iteration.
-fn run_ec(ctx: &Context, i: u64) -> Results {
-    let mut c = Course::load(i);
+/// given context and course iteration of a curriculum, along with the size
+/// of the grammar that ec was given. If given a storage directory, the
+/// input to ec is kept there rather than written to a temporary file.
+fn run_ec(ctx: &Context<Combinators>,
+          i: u64,
+          curriculum: &str,
+          bin: &str,
+          storage: Option<&str>,
+          limits: &Limits)
+          -> Result<(Results, usize), EcError> {
+    let mut c = Course::load(curriculum, i)?;
     c.merge(ctx);
+    let grammar_size = c.grammar_size();
     if LOG_LEVEL & 8 != 0 {
         println!("EC INPUT:\n{}", serde_json::to_string_pretty(&c).unwrap())
     }
-    let output;
-    if STORE_INPUTS {
-        let path = store_input_path(i);
-        c.save_perm(&path);
-        output = Command::new(ec_bin())
-            .arg(path)
-            .output()
-            .expect("run ec");
+    let results = if let Some(store_dir) = storage {
+        let path = store_input_path(store_dir, i);
+        c.save_perm(&path)?;
+        exec_ec(bin, &path, i, limits)?
     } else {
-        let (tmp_dir, path) = c.save(i);
-        output = Command::new(ec_bin())
-            .arg(path)
-            .output()
-            .expect("run ec");
+        let (tmp_dir, path) = c.save(i)?;
+        let results = exec_ec(bin, &path, i, limits)?;
         drop(tmp_dir); // we can delete the temporary directory after ec has run
-    }
-    if !output.status.success() {
-        let err = String::from_utf8(output.stderr).unwrap();
-        panic!("ec failed in phase {}: {}", i, err)
-    }
-    let raw_results = String::from_utf8(output.stdout).expect("read ec output");
-    if LOG_LEVEL & 4 != 0 {
-        let err = if output.stderr.is_empty() { String::from("") } else {
-            let raw_err = String::from_utf8(output.stderr).expect("read ec err");
-            format!("EC ERROR:\n{}\n", raw_err)
-        };
-        println!("{}EC OUTPUT:\n{}", err, raw_results)
-    }
-    Results::from_string(raw_results)
+        results
+    };
+    Ok((results, grammar_size))
 }
 
 /// `exprs_in_context` takes a set of items in the context as given by
 /// `Context::get()` or `Context::explore()` and returns the combinators
 /// contained in those that are readable by ec.
-fn exprs_in_context(ctx: Vec<(usize, &'static str, Rc<String>)>) -> HashMap<String, usize> {
+fn exprs_in_context(ctx: Vec<(usize, &'static str, Rc<Combinators>)>) -> HashMap<String, usize> {
     ctx.into_iter()
         .filter(|&(_, mech, _)| mech == "ec")
-        .map(|(id, _, d)| {
-                 let item: Vec<String> =
-                     serde_json::from_str(&d).expect("parse combinators from context");
-                 (id, item)
-             })
-        .flat_map(|(id, item)| item.into_iter().map(move |expr| (expr, id)))
+        .flat_map(|(id, _, d)| {
+                      let exprs: Vec<String> = d.0.clone();
+                      exprs.into_iter().map(move |expr| (expr, id))
+                  })
         .collect()
 }
 
@@ -318,135 +445,306 @@ fn exprs_in_context(ctx: Vec<(usize, &'static str, Rc<String>)>) -> HashMap<Stri
 /// `Context::get()` or `Context::explore()` and a vector of combinators.
 /// It returns a vector of the same size as exprs, with `Some(id)` if a match
 /// was found or None otherwise.
-fn find_exprs_in_context(ctx: Vec<(usize, &'static str, Rc<String>)>,
+fn find_exprs_in_context(ctx: Vec<(usize, &'static str, Rc<Combinators>)>,
                          exprs: &[&str])
                          -> Vec<Option<usize>> {
     let exprs_in_ctx = exprs_in_context(ctx);
     exprs
         .iter()
-        .map(|&e| match exprs_in_ctx.get(e) {
-                 Some(id) => Some(*id),
-                 None => None,
-             })
+        .map(|&e| exprs_in_ctx.get(e).cloned())
         .collect()
 }
 
 /// `find_expr_in_context` is like `find_exprs_in_context` but for a
 /// single combinator.
-fn find_expr_in_context(ctx: Vec<(usize, &'static str, Rc<String>)>,
+fn find_expr_in_context(ctx: Vec<(usize, &'static str, Rc<Combinators>)>,
                         expr: &str)
                         -> Option<usize> {
     find_exprs_in_context(ctx, &[expr])[0]
 }
 
-/// mech is the ec mechanism as it should be registered/used by an Skn
+/// `find_nearest_expr_in_context` takes items as given by
+/// `Context::explore_ranked()` and a combinator. It returns the id and
+/// distance of the first item, and so the nearest, that contains it.
+fn find_nearest_expr_in_context(ctx: Vec<(usize, &'static str, Rc<Combinators>, usize)>,
+                                expr: &str)
+                                -> Option<(usize, usize)> {
+    ctx.into_iter()
+        .find(|&(id, mech, ref d, _)| {
+                  find_expr_in_context(vec![(id, mech, d.clone())], expr).is_some()
+              })
+        .map(|(id, _, _, dist)| (id, dist))
+}
+
+/// Learned is what the ec mechanism has seen of a learned combinator.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Learned {
+    /// the number of iterations that ec learned the combinator in.
+    times: u64,
+    /// the best log likelihood that ec gave the combinator.
+    best: f64,
+    /// the latest iteration that ec learned the combinator in.
+    last: u64,
+}
+
+/// Ec is the ec mechanism as it should be registered/used by an Skn
 /// object. It wraps running ec with updating item access counts and adding
-/// a new item where appropriate.
-pub fn mech(ctx: Context, i: u64) {
-    // run ec
-    let results = run_ec(&ctx, i);
-    let failures: Vec<&String> = results
-        .programs
-        .iter()
-        .filter(|p| p.result.is_none())
-        .map(|p| &p.task)
-        .collect();
-    if LOG_LEVEL & 1 != 0 {
-        println!("ec at phase {} with got hit-rate {}/{}. failed: {:?}",
-                 i,
-                 results.hit_rate,
-                 results.programs.len(),
-                 failures);
-    }
-    if LOG_LEVEL & 2 != 0 {
-        println!("   using ctx {:?}", exprs_in_context(ctx.get()));
-    }
-
-    // retrieve learned combs
-    let prims = primitives();
-    let mut learned: Vec<(String, f64)> = results
-        .grammar
-        .iter()
-        .map(|c| (c.expr.clone(), c.log_likelihood))
-        .filter(|c| !prims.contains(&c.0) && c.1.is_finite())
-        .collect();
-    if EC_GRAMMAR_INCLUDE_PROGS {
-        learned.extend(results
-                           .programs
-                           .iter()
-                           .filter(|t| t.result.is_some())
-                           .map(|t| {
-                                    let r = &t.result;
-                                    let r = r.clone().unwrap();
-                                    (r.expr, r.log_probability)
-                                })
-                           .filter(|c| !prims.contains(&c.0) && c.1.is_finite()));
-    }
-
-    // early return if no useful results
-    if learned.is_empty() {
-        return;
-    }
-
-    // orient to most probable comb
-    let mut ctx = ctx;
+/// a new item where appropriate, and keeps track of the combinators that ec
+/// has learned over every iteration. What has been learned is reported when
+/// the run finishes, and kept in checkpoints so that a resumed run reports
+/// it all.
+#[derive(Debug, Default)]
+pub struct Ec {
+    prims: HashSet<String>,
+    learned: BTreeMap<String, Learned>,
+    metrics: Metrics,
+    store_inputs: bool,
+    storage: Option<String>,
+    curriculum: Option<String>,
+    bin: Option<String>,
+    limits: Limits,
+}
+
+impl Ec {
+    /// new creates the ec mechanism, which stores the input it gives to ec
+    /// in each iteration (see `store_inputs`).
+    pub fn new() -> Ec {
+        Ec {
+            store_inputs: true,
+            ..Ec::default()
+        }
+    }
+    /// `store_inputs` sets whether the input given to ec in each iteration
+    /// is kept, in the directory given by the `$EC_STORAGE` environment
+    /// variable (or ec_storage).
+    pub fn store_inputs(&mut self, store: bool) {
+        self.store_inputs = store;
+    }
+    /// `store_inputs_in` makes the input given to ec in each iteration be
+    /// kept in the given directory, rather than that given by
+    /// `$EC_STORAGE`.
+    pub fn store_inputs_in(&mut self, dir: &str) {
+        self.store_inputs = true;
+        self.storage = Some(String::from(dir));
+    }
+    /// `use_curriculum` makes ec learn from the courses of the given
+    /// directory, rather than that given by `$EC_CURRICULUM`.
+    pub fn use_curriculum(&mut self, dir: &str) {
+        self.curriculum = Some(String::from(dir));
+    }
+    /// `use_bin` makes the given ec binary be run, rather than that given
+    /// by `$EC`.
+    pub fn use_bin(&mut self, bin: &str) {
+        self.bin = Some(String::from(bin));
+    }
+    /// `set_limits` bounds how long ec may run and what it may use in each
+    /// iteration, and how often it is retried. By default, ec is run once
+    /// with no limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+    /// `metrics_tsv_to` makes ec write a row of tab-separated values for
+    /// every task it solves: the task, time, log probability, seconds taken
+    /// by the ec run, expression, and iteration.
+    pub fn metrics_tsv_to<W>(&mut self, w: W)
+        where W: Write + 'static
+    {
+        self.metrics.tsv_to(Box::new(w));
+    }
+    /// `metrics_jsonl_to` makes ec write a line of JSON after every
+    /// iteration, with the hit rate, failed tasks, each task's result, and
+    /// the sizes of the context, network, and grammar.
+    pub fn metrics_jsonl_to<W>(&mut self, w: W)
+        where W: Write + 'static
     {
-        let most_probable  = &learned
+        self.metrics.jsonl_to(Box::new(w));
+    }
+}
+
+impl Mechanism<Combinators> for Ec {
+    fn init(&mut self) {
+        self.prims = primitives();
+    }
+    fn step(&mut self, ctx: Context<Combinators>, i: u64) -> Result<(), Box<dyn Error>> {
+        // run ec
+        let start = Instant::now();
+        let storage = if self.store_inputs {
+            Some(self.storage.clone().unwrap_or_else(default_storage))
+        } else {
+            None
+        };
+        let bin = self.bin.clone().unwrap_or_else(ec_bin);
+        let run = self.curriculum
+            .clone()
+            .map_or_else(default_curriculum, Ok)
+            .and_then(|curriculum| run_ec(&ctx, i, &curriculum, &bin, storage.as_deref(), &self.limits));
+        let (results, grammar_size) = match run {
+            Ok(run) => run,
+            Err(err) => {
+                self.metrics.record_failure(i, &err);
+                return Err(Box::new(err));
+            }
+        };
+        let seconds = start.elapsed().as_secs_f64();
+        let failures: Vec<&str> = results
+            .programs
+            .iter()
+            .filter(|p| p.result.is_none())
+            .map(|p| p.task.as_str())
+            .collect();
+        if LOG_LEVEL & 1 != 0 {
+            println!("ec at phase {} with got hit-rate {}/{}. failed: {:?}",
+                     i,
+                     results.hit_rate,
+                     results.programs.len(),
+                     failures);
+        }
+        if LOG_LEVEL & 2 != 0 {
+            println!("   using ctx {:?}", exprs_in_context(ctx.get()));
+        }
+        self.metrics.record(&Iteration {
+            iteration: i,
+            hit_rate: results.hit_rate,
+            failed: failures,
+            tasks: &results.programs,
+            context_size: Some(ctx.get().len()),
+            network_size: Some(ctx.size()),
+            grammar_size,
+            seconds,
+            ablation: ctx.ablation(),
+        });
+        if !results.programs.is_empty() {
+            ctx.report(results.hit_rate as f64 / results.programs.len() as f64);
+        }
+
+        // retrieve learned combs
+        let prims = &self.prims;
+        let mut learned: Vec<(String, f64)> = results
+            .grammar
+            .iter()
+            .map(|c| (c.expr.clone(), c.log_likelihood))
+            .filter(|c| !prims.contains(&c.0) && c.1.is_finite())
+            .collect();
+        if EC_GRAMMAR_INCLUDE_PROGS {
+            learned.extend(results
+                               .programs
+                               .iter()
+                               .filter(|t| t.result.is_some())
+                               .map(|t| {
+                                        let r = &t.result;
+                                        let r = r.clone().unwrap();
+                                        (r.expr, r.log_probability)
+                                    })
+                               .filter(|c| !prims.contains(&c.0) && c.1.is_finite()));
+        }
+
+        for &(ref expr, log_likelihood) in &learned {
+            let stats = self.learned.entry(expr.clone()).or_insert(Learned {
+                times: 0,
+                best: f64::NEG_INFINITY,
+                last: i,
+            });
+            stats.times += 1;
+            stats.best = stats.best.max(log_likelihood);
+            stats.last = i;
+        }
+
+        // early return if no useful results
+        if learned.is_empty() {
+            return Ok(());
+        }
+
+        // orient to most probable comb
+        let mut ctx = ctx;
+        {
+            let most_probable  = &learned
+                .iter()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap()
+                .0;
+            let result = find_nearest_expr_in_context(ctx.explore_ranked(1), most_probable);
+            if let Some((id, dist)) = result {
+                if LOG_LEVEL & 2 != 0 {
+                    println!("   ctx.orient({}) at distance {}", id, dist);
+                }
+                ctx.orient(id);
+                ctx = ctx.update();
+            }
+        }
+
+        // make accesses ~ usage
+        learned.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap()); // reversed sort
+        let exprs: Vec<&str> = learned.iter().map(|l| l.0.as_str()).collect();
+        let findings = find_exprs_in_context(ctx.get(), &exprs);
+        let mut access_info: Vec<(&String, f64, usize)> = learned
+            .iter()
+            .zip(findings)
+            .filter(|&(_, o)| o.is_some())
+            .map(|(&(ref s, p), o)| (s, p, o.unwrap())) // s, p, id
+            .filter(|&(_, p, _)| p.is_finite())
+            .collect();
+        let least = access_info
             .iter()
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .unwrap()
-            .0;
-        let result = find_expr_in_context(ctx.explore(), most_probable);
-        if let Some(id) = result {
+            .map(|&(_, p, _)| p)
+            .fold(f64::INFINITY, f64::min);
+        let most = access_info
+            .iter()
+            .map(|&(_, p, _)| p)
+            .fold(f64::NEG_INFINITY, f64::max);
+        access_info = access_info
+            .into_iter()
+            .map(|(s, p, id)| (s, EC_ACCESS_FACTOR * (p-least)/(most-least), id)) // normalize
+            .filter(|&(_, f, _)| f.is_finite())
+            .collect();
+        for comb in &access_info {
+            ctx.add_item_count(comb.2, comb.1 as u64);
+        }
+
+        // add item with probable combs, excluding primitives and combs in context
+        let exprs_in_ctx = exprs_in_context(ctx.explore());
+        let new_combs: Vec<String> = learned // already sorted by prob
+            .iter()
+            .map(|(s, _)| s)
+            .filter(|&s| !prims.contains(s) && !exprs_in_ctx.contains_key(s))
+            .take(EC_MAX_IN_ARTIFACT)
+            .cloned()
+            .collect();
+        if !new_combs.is_empty() {
             if LOG_LEVEL & 2 != 0 {
-                println!("   ctx.orient({})", id);
+                println!("   ctx.grow({:?})", new_combs);
             }
-            ctx.orient(id);
-            ctx = ctx.update();
+            ctx.grow(Combinators(new_combs));
         }
+        Ok(())
     }
-
-    // make accesses ~ usage
-    learned.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap()); // reversed sort
-    let exprs: Vec<&str> = learned.iter().map(|l| l.0.as_str()).collect();
-    let findings = find_exprs_in_context(ctx.get(), &exprs);
-    let mut access_info: Vec<(&String, f64, usize)> = learned
-        .iter()
-        .zip(findings.into_iter())
-        .filter(|&(_, o)| o.is_some())
-        .map(|(&(ref s, p), o)| (s, p, o.unwrap())) // s, p, id
-        .filter(|&(_, p, _)| p.is_finite())
-        .collect();
-    let least = access_info
-        .iter()
-        .map(|&(_, p, _)| p)
-        .fold(f64::INFINITY, f64::min);
-    let most = access_info
-        .iter()
-        .map(|&(_, p, _)| p)
-        .fold(f64::NEG_INFINITY, f64::max);
-    access_info = access_info
-        .into_iter()
-        .map(|(s, p, id)| (s, EC_ACCESS_FACTOR * (p-least)/(most-least), id)) // normalize
-        .filter(|&(_, f, _)| f.is_finite())
-        .collect();
-    for comb in &access_info {
-        ctx.add_item_count(comb.2, comb.1 as u64);
-    }
-
-    // add item with probable combs, excluding primitives and combs in context
-    let exprs_in_ctx = exprs_in_context(ctx.explore());
-    let new_combs: Vec<String> = learned // already sorted by prob
-        .iter()
-        .map(|&(ref s, _)| s)
-        .filter(|&s| !prims.contains(s) && !exprs_in_ctx.contains_key(s))
-        .take(EC_MAX_IN_ARTIFACT)
-        .cloned()
-        .collect();
-    if !new_combs.is_empty() {
-        ctx.grow(json!(new_combs).to_string());
-        if LOG_LEVEL & 2 != 0 {
-            println!("   ctx.grow({:?})", new_combs);
+    fn finish(&mut self) {
+        if LOG_LEVEL & 1 != 0 {
+            let mut learned: Vec<(&String, &Learned)> = self.learned.iter().collect();
+            learned.sort_by(|a, b| b.1.times.cmp(&a.1.times).then(a.0.cmp(b.0)));
+            println!("ec learned {} combinators:", learned.len());
+            for (expr, stats) in learned {
+                println!("   {} in {} phases (best log likelihood {:.3}, last in phase {})",
+                         expr,
+                         stats.times,
+                         stats.best,
+                         stats.last);
+            }
         }
     }
+    fn save(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(&self.learned).ok()
+    }
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.learned = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
+
+/// mech runs ec once as a mechanism that keeps nothing between iterations,
+/// as the ec mechanism did before Ec.
+#[deprecated(note = "register ec::Ec::new() instead, which keeps what ec has learned")]
+pub fn mech(ctx: Context<Combinators>, i: u64) -> Result<(), Box<dyn Error>> {
+    let mut ec = Ec::new();
+    ec.init();
+    ec.step(ctx, i)
 }
@@ -0,0 +1,306 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+
+extern crate serde_json;
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::time::Instant;
+
+use knowledge::Skn;
+use knowledge::strategy::Ablation;
+use super::{Ec, EcError, Limits, LOG_LEVEL, embryo, exec_ec, iter_max, store_input_path};
+use super::course::{Course, course_path};
+use super::metrics::{Iteration, Metrics};
+
+/// the ablations of the contextual condition, and the names of their
+/// conditions.
+static ABLATIONS: [(Ablation, &str); 3] = [(Ablation::Whole, "contextual_whole"),
+                                           (Ablation::Embryo, "contextual_embryo"),
+                                           (Ablation::Random, "contextual_random")];
+
+/// Compare runs ec under contextual learning and under three baselines, so
+/// that they can be compared:
+///  - primitive: a single iteration of ec on each of the first phases of the
+///    curriculum, with no learned grammar.
+///  - `specialized_per_phase`: ec on each of the first phases.
+///  - `specialized_full_domain`: ec on every task of the domain at once.
+///  - contextual: the ec mechanism in a knowledge network over the whole
+///    curriculum, after which, as for primitive, a single iteration of ec is
+///    run on each of the inputs it gave ec in its last phases.
+///
+/// With ablations, the contextual condition is also run with each Ablation,
+/// as `contextual_whole`, `contextual_embryo` and `contextual_random`.
+///
+/// Each condition gets metrics written to out as `<condition>.tsv` and
+/// `<condition>.jsonl` (see `Ec::metrics_tsv_to` and
+/// `Ec::metrics_jsonl_to`), and every phase of every condition is
+/// collected into compare.json. Phases are numbered from 1 in every
+/// condition. A contextual condition also keeps the inputs of its network
+/// run in `<condition>_inputs`, and that run's own metrics as
+/// `<condition>_network.tsv` and `<condition>_network.jsonl`.
+#[derive(Clone, Debug)]
+pub struct Compare {
+    /// the curriculum directory, like curriculum/ec.
+    pub curriculum: String,
+    /// the ec binary, like that given by `ec_bin`.
+    pub ec: String,
+    /// the ec binary that runs a single iteration, like that given by
+    /// `ec_one_bin`.
+    pub ec_one: String,
+    /// a course with every task of the domain, like curriculum/all.json.
+    pub all: String,
+    /// the number of phases of the curriculum that the per-phase baselines
+    /// are run on, and of the last phases of a contextual run that are
+    /// measured.
+    pub phases: u64,
+    /// the directory that results are written to.
+    pub out: String,
+    /// the seed for the knowledge network of the contextual condition.
+    pub seed: Option<u64>,
+    /// whether to also run each Ablation of the contextual condition.
+    pub ablations: bool,
+    /// the Limits of each run of ec, in every condition.
+    pub limits: Limits,
+}
+
+/// Condition is the results of one condition of a comparison, as written
+/// to compare.json.
+#[derive(Serialize)]
+struct Condition<'a> {
+    condition: &'a str,
+    iterations: Vec<serde_json::Value>,
+}
+
+impl Compare {
+    /// run performs every condition and writes the results. A phase of a
+    /// baseline in which ec fails is reported and recorded in the metrics
+    /// with the reason, as is a failed iteration of a contextual condition.
+    pub fn run(&self) -> Result<(), EcError> {
+        fs::create_dir_all(&self.out)?;
+        let courses = (1..self.phases + 1)
+            .map(|i| course_path(&self.curriculum, i))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.baseline("primitive", &self.ec_one, &courses)?;
+        self.baseline("specialized_per_phase", &self.ec, &courses)?;
+        self.baseline("specialized_full_domain", &self.ec, slice::from_ref(&self.all))?;
+        self.contextual("contextual", None)?;
+        let mut conditions = vec!["primitive",
+                                  "specialized_per_phase",
+                                  "specialized_full_domain",
+                                  "contextual"];
+        if self.ablations {
+            for &(ablation, condition) in &ABLATIONS {
+                self.contextual(condition, Some(ablation))?;
+                conditions.push(condition);
+            }
+        }
+        self.collect(&conditions)
+    }
+    fn path(&self, condition: &str, ext: &str) -> PathBuf {
+        Path::new(&self.out).join(format!("{}.{}", condition, ext))
+    }
+    /// metrics creates the metrics files for a condition.
+    fn metrics(&self, condition: &str) -> Result<(File, File), EcError> {
+        let tsv = File::create(self.path(condition, "tsv"))?;
+        let jsonl = File::create(self.path(condition, "jsonl"))?;
+        Ok((tsv, jsonl))
+    }
+    /// baseline runs an ec binary on each input in turn, outside of any
+    /// knowledge network.
+    fn baseline(&self, condition: &str, bin: &str, inputs: &[String]) -> Result<(), EcError> {
+        let (tsv, jsonl) = self.metrics(condition)?;
+        let mut metrics = Metrics::default();
+        metrics.tsv_to(Box::new(tsv));
+        metrics.jsonl_to(Box::new(jsonl));
+        for (i, input) in (1..).zip(inputs) {
+            let start = Instant::now();
+            let results = match exec_ec(bin, input, i, &self.limits) {
+                Ok(results) => results,
+                Err(err) => {
+                    eprintln!("{} failed in phase {}: {}", condition, i, err);
+                    metrics.record_failure(i, &err);
+                    continue;
+                }
+            };
+            let seconds = start.elapsed().as_secs_f64();
+            if LOG_LEVEL & 1 != 0 {
+                println!("{} at phase {} got hit-rate {}/{}",
+                         condition,
+                         i,
+                         results.hit_rate,
+                         results.programs.len());
+            }
+            metrics.record(&Iteration {
+                iteration: i,
+                hit_rate: results.hit_rate,
+                failed: results
+                    .programs
+                    .iter()
+                    .filter(|p| p.result.is_none())
+                    .map(|p| p.task.as_str())
+                    .collect(),
+                tasks: &results.programs,
+                context_size: None,
+                network_size: None,
+                grammar_size: Course::read(input)?.grammar_size(),
+                seconds,
+                ablation: None,
+            });
+        }
+        Ok(())
+    }
+    /// contextual runs the ec mechanism in a knowledge network over the
+    /// whole curriculum, under an Ablation if given, keeping each input it
+    /// gives ec. The condition is then measured like the primitive
+    /// baseline, by running `ec_one` on the inputs of the last phases.
+    fn contextual(&self, condition: &str, ablation: Option<Ablation>) -> Result<(), EcError> {
+        let t = iter_max(&self.curriculum)?;
+        let embryo = embryo(&self.curriculum)?;
+        let mut skn = match self.seed {
+            Some(seed) => Skn::with_seed(embryo, t, seed),
+            None => Skn::new(embryo, t),
+        };
+        if let Some(ablation) = ablation {
+            skn.set_ablation(ablation);
+        }
+        let storage = Path::new(&self.out).join(format!("{}_inputs", condition));
+        fs::create_dir_all(&storage)?;
+        let storage = storage.to_string_lossy().into_owned();
+        let (tsv, jsonl) = self.metrics(&format!("{}_network", condition))?;
+        let mut ec = Ec::new();
+        ec.use_curriculum(&self.curriculum);
+        ec.use_bin(&self.ec);
+        ec.store_inputs_in(&storage);
+        ec.set_limits(self.limits.clone());
+        ec.metrics_tsv_to(tsv);
+        ec.metrics_jsonl_to(jsonl);
+        skn.register("ec", ec);
+        skn.run();
+        let first = t.saturating_sub(self.phases) + 1;
+        let inputs: Vec<String> = (first..t + 1).map(|i| store_input_path(&storage, i)).collect();
+        self.baseline(condition, &self.ec_one, &inputs)
+    }
+    /// collect gathers the metrics of every condition into compare.json.
+    fn collect(&self, conditions: &[&str]) -> Result<(), EcError> {
+        let mut collected = Vec::new();
+        for &condition in conditions {
+            let f = File::open(self.path(condition, "jsonl"))?;
+            let mut iterations = Vec::new();
+            for line in BufReader::new(f).lines() {
+                let iteration = serde_json::from_str(&line?)
+                    .map_err(|err| EcError::Parse("metrics", err))?;
+                iterations.push(iteration);
+            }
+            collected.push(Condition {
+                               condition,
+                               iterations,
+                           });
+        }
+        let f = File::create(Path::new(&self.out).join("compare.json"))?;
+        serde_json::to_writer_pretty(f, &collected).map_err(|err| EcError::json("comparison", err))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    extern crate serde_json;
+
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    use tempdir::TempDir;
+
+    use super::Compare;
+    use super::super::Limits;
+
+    /// stub writes an executable script standing in for ec, which solves a
+    /// task in every input except those of the second phase of the
+    /// curriculum, where it fails.
+    fn stub(dir: &Path, name: &str, expr: &str) -> String {
+        let path = dir.join(name);
+        let mut f = File::create(&path).unwrap();
+        writeln!(f,
+                 r#"#!/bin/sh
+case "$1" in *course_02*) echo "no luck" >&2; exit 3;; esac
+echo '{{"grammar": [], "programs": [{{"task": "nospaces", "result": {{"expr": "{}", "log_probability": -2.0, "time": 0.5}}}}, {{"task": "SICP", "result": null}}], "log_bic": null, "hit_rate": 1}}'"#,
+                 expr)
+            .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn compare_conditions() {
+        let tmp_dir = TempDir::new("compare").unwrap();
+        let out = tmp_dir.path().join("out");
+        let compare = Compare {
+            curriculum: String::from("curriculum/ec_reiter"),
+            ec: stub(tmp_dir.path(), "ec", "full"),
+            ec_one: stub(tmp_dir.path(), "ec_one", "one"),
+            all: String::from("curriculum/all.json"),
+            phases: 3,
+            out: out.to_string_lossy().into_owned(),
+            seed: Some(7),
+            ablations: false,
+            limits: Limits::default(),
+        };
+        compare.run().unwrap();
+
+        let collected: serde_json::Value =
+            serde_json::from_reader(File::open(out.join("compare.json")).unwrap()).unwrap();
+        let conditions: Vec<&str> = collected
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["condition"].as_str().unwrap())
+            .collect();
+        assert_eq!(conditions,
+                   vec!["primitive", "specialized_per_phase", "specialized_full_domain", "contextual"]);
+        let phases = |c: usize| -> Vec<(u64, bool)> {
+            collected[c]["iterations"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|it| (it["iteration"].as_u64().unwrap(), it.get("error").is_some()))
+                .collect()
+        };
+        // the second phase failed for each per-phase baseline, and is
+        // recorded with the reason.
+        assert_eq!(phases(0), vec![(1, false), (2, true), (3, false)]);
+        assert_eq!(phases(1), vec![(1, false), (2, true), (3, false)]);
+        assert_eq!(phases(2), vec![(1, false)]);
+        assert!(collected[0]["iterations"][1]["error"].as_str().unwrap().contains("no luck"));
+
+        // contextual is measured by ec_one on the inputs of the network's
+        // last three iterations.
+        assert_eq!(phases(3), vec![(1, false), (2, false), (3, false)]);
+        let tsv = fs::read_to_string(out.join("contextual.tsv")).unwrap();
+        assert!(tsv.lines().all(|l| l.split('\t').nth(4) == Some("one")));
+        for i in 6..9 {
+            assert!(out.join("contextual_inputs").join(format!("input_contextual_{}.json", i)).exists());
+        }
+        let network = fs::read_to_string(out.join("contextual_network.jsonl")).unwrap();
+        assert_eq!(network.lines().count(), 8);
+    }
+}
@@ -0,0 +1,157 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+
+extern crate serde_json;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+/// `EcError` is the reason that ec could not be run or its results used.
+#[derive(Debug)]
+pub enum EcError {
+    /// the ec binary, given by its path, could not be found.
+    MissingBinary(String),
+    /// no curriculum directory could be found.
+    MissingCurriculum,
+    /// a file could not be read or written, or ec could not be run.
+    Io(io::Error),
+    /// something, such as a course file or ec's output, was not valid JSON
+    /// of the right shape.
+    Parse(&'static str, serde_json::Error),
+    /// ec exited unsuccessfully in a phase, with an exit code if it wasn't
+    /// killed by a signal, or with the signal if it was.
+    Exit {
+        phase: u64,
+        code: Option<i32>,
+        signal: Option<i32>,
+        stderr: String,
+    },
+    /// ec was killed for running past its timeout in a phase.
+    Timeout { phase: u64, after: Duration },
+    /// ec was killed for using more than its CPU limit, in seconds, in a
+    /// phase.
+    CpuLimit { phase: u64, seconds: u64 },
+    /// ec reported running out of memory in a phase, while limited to this
+    /// many bytes of it.
+    MemoryLimit { phase: u64, bytes: u64, stderr: String },
+}
+
+impl EcError {
+    /// json makes the error for reading or writing JSON: Io if the reading
+    /// or writing failed, and otherwise Parse.
+    pub fn json(what: &'static str, err: serde_json::Error) -> EcError {
+        if err.is_io() { EcError::Io(err.into()) } else { EcError::Parse(what, err) }
+    }
+    /// exit makes the error for ec exiting unsuccessfully.
+    pub fn exit(phase: u64, status: ExitStatus, stderr: &[u8]) -> EcError {
+        #[cfg(unix)]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.signal()
+        };
+        #[cfg(not(unix))]
+        let signal = None;
+        EcError::Exit {
+            phase,
+            code: status.code(),
+            signal,
+            stderr: String::from_utf8_lossy(stderr).into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for EcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EcError::MissingBinary(ref bin) => write!(f, "could not find ec binary {:?}", bin),
+            EcError::MissingCurriculum => write!(f, "could not find ec curriculum"),
+            EcError::Timeout { phase, after } => {
+                write!(f, "ec timed out after {:?} in phase {}", after, phase)
+            }
+            EcError::CpuLimit { phase, seconds } => {
+                write!(f, "ec exceeded its CPU limit of {}s in phase {}", seconds, phase)
+            }
+            EcError::MemoryLimit { phase, bytes, ref stderr } => {
+                write!(f,
+                       "ec ran out of memory in phase {}, under its limit of {} MB",
+                       phase,
+                       bytes >> 20)?;
+                if stderr.is_empty() { Ok(()) } else { write!(f, ": {}", stderr.trim_end()) }
+            }
+            EcError::Io(ref err) => write!(f, "{}", err),
+            EcError::Parse(what, ref err) => write!(f, "parse {}: {}", what, err),
+            EcError::Exit { phase, code, signal, ref stderr } => {
+                match (code, signal) {
+                    (Some(code), _) => write!(f, "ec failed in phase {} with code {}", phase, code)?,
+                    (None, Some(signal)) => {
+                        write!(f, "ec was killed by signal {} in phase {}", signal, phase)?
+                    }
+                    (None, None) => write!(f, "ec was killed in phase {}", phase)?,
+                }
+                if stderr.is_empty() { Ok(()) } else { write!(f, ": {}", stderr.trim_end()) }
+            }
+        }
+    }
+}
+
+impl error::Error for EcError {}
+
+impl From<io::Error> for EcError {
+    fn from(err: io::Error) -> EcError {
+        EcError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use std::io::{self, Write};
+
+    use super::EcError;
+
+    /// Full is a writer that always fails, like a full disk.
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "no space left"))
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_errors() {
+        let err = serde_json::to_writer(Full, &vec![1, 2]).unwrap_err();
+        match EcError::json("summary", err) {
+            EcError::Io(ref err) => assert_eq!(err.to_string(), "no space left"),
+            other => panic!("expected an io error, got {:?}", other),
+        }
+        let err = serde_json::from_str::<Vec<u8>>("[1,").unwrap_err();
+        match EcError::json("summary", err) {
+            EcError::Parse("summary", _) => (),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,440 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+
+#[cfg(unix)]
+extern crate libc;
+
+use std::io::{self, Read};
+use std::mem;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::EcError;
+
+/// how often a running ec is checked on for a timeout.
+const POLL: Duration = Duration::from_millis(50);
+
+/// what, in lowercase, a program reports on stderr when it runs out of
+/// memory: OCaml's exception, and the messages for ENOMEM.
+#[cfg(target_os = "linux")]
+static OUT_OF_MEMORY: [&str; 4] = ["out_of_memory", "out of memory", "cannot allocate memory",
+                                   "enomem"];
+
+/// how long ec's output is waited for once it has exited. Anything ec left
+/// running may hold its pipes open for longer.
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+
+/// Limits bounds the ec subprocess, so that a stuck or runaway ec fails
+/// its iteration rather than hanging the experiment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Limits {
+    /// the wall-clock time ec may run for before it is killed, along with
+    /// anything it started. For that, ec is run in its own process group,
+    /// so an interrupt from the terminal doesn't reach it.
+    pub timeout: Option<Duration>,
+    /// the number of times a failed run of ec is tried again. This is the
+    /// retry policy for ec: a run with `OnFailure::Retry` as well steps
+    /// ec again only once all of these have failed, so ec may run up to
+    /// (1 + retries) times per step.
+    pub retries: u32,
+    /// the wait before the first retry, doubled for each retry after.
+    pub backoff: Duration,
+    /// the CPU time, in seconds, ec may use. Only applied on Linux.
+    pub cpu_seconds: Option<u64>,
+    /// the address space, in bytes, ec may use. Only applied on Linux.
+    pub memory_bytes: Option<u64>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            timeout: None,
+            retries: 0,
+            backoff: Duration::from_secs(1),
+            cpu_seconds: None,
+            memory_bytes: None,
+        }
+    }
+}
+
+/// Output is what a finished ec gave.
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl Limits {
+    /// run runs an ec binary on the input at path within the limits, with
+    /// retries, giving the output of the first run that exits successfully.
+    pub fn run(&self, bin: &str, path: &str, phase: u64) -> Result<Output, EcError> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let err = match self.run_once(bin, path, phase) {
+                Ok(output) => {
+                    if output.status.success() {
+                        return Ok(output);
+                    }
+                    self.failure(phase, output.status, &output.stderr)
+                }
+                Err(err) => err,
+            };
+            if attempt >= self.retries {
+                return Err(err);
+            }
+            if let EcError::MissingBinary(_) = err {
+                return Err(err);
+            }
+            attempt += 1;
+            eprintln!("retrying ec in phase {} ({} of {}) in {:?}: {}",
+                      phase,
+                      attempt,
+                      self.retries,
+                      backoff,
+                      err);
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+    /// `run_once` runs ec a single time, killing it if it times out.
+    fn run_once(&self, bin: &str, path: &str, phase: u64) -> Result<Output, EcError> {
+        let mut cmd = Command::new(bin);
+        cmd.arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            if self.timeout.is_some() {
+                cmd.process_group(0);
+            }
+        }
+        self.restrict(&mut cmd);
+        let mut child = match cmd.spawn() {
+            Ok(child) => child,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(EcError::MissingBinary(String::from(bin)))
+            }
+            Err(err) => return Err(EcError::Io(err)),
+        };
+        // read while waiting, so that ec doesn't block on a full pipe.
+        let stdout = Drain::new(child.stdout.take());
+        let stderr = Drain::new(child.stderr.take());
+        let waited = self.wait(&mut child);
+        if !matches!(waited, Ok(Some(_))) {
+            // killing fails only if ec has just exited, so either way it
+            // is reaped here.
+            kill(&mut child);
+            let _ = child.wait();
+        }
+        let (stdout, stderr) = (stdout.finish(DRAIN_GRACE), stderr.finish(DRAIN_GRACE));
+        match waited? {
+            Some(status) => {
+                Ok(Output {
+                       status,
+                       stdout,
+                       stderr,
+                   })
+            }
+            None => {
+                Err(EcError::Timeout {
+                        phase,
+                        after: self.timeout.unwrap(),
+                    })
+            }
+        }
+    }
+    /// failure makes the error for ec exiting unsuccessfully, naming the
+    /// limit it hit if it clearly hit one: SIGXCPU is sent for the CPU
+    /// limit, while the memory limit only makes allocations fail, so it is
+    /// named only if ec reports running out of memory.
+    #[cfg(target_os = "linux")]
+    fn failure(&self, phase: u64, status: ExitStatus, stderr: &[u8]) -> EcError {
+        let err = EcError::exit(phase, status, stderr);
+        if let EcError::Exit { signal, ref stderr, .. } = err {
+            if let (Some(seconds), Some(libc::SIGXCPU)) = (self.cpu_seconds, signal) {
+                return EcError::CpuLimit { phase, seconds };
+            }
+            if let Some(bytes) = self.memory_bytes {
+                let stderr_lower = stderr.to_lowercase();
+                if OUT_OF_MEMORY.iter().any(|m| stderr_lower.contains(m)) {
+                    return EcError::MemoryLimit {
+                               phase,
+                               bytes,
+                               stderr: stderr.clone(),
+                           };
+                }
+            }
+        }
+        err
+    }
+    #[cfg(not(target_os = "linux"))]
+    fn failure(&self, phase: u64, status: ExitStatus, stderr: &[u8]) -> EcError {
+        EcError::exit(phase, status, stderr)
+    }
+    /// wait waits for ec to exit, giving None if it times out first.
+    fn wait(&self, child: &mut Child) -> io::Result<Option<ExitStatus>> {
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return child.wait().map(Some),
+        };
+        let began = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Some(status));
+            }
+            if began.elapsed() >= timeout {
+                return Ok(None);
+            }
+            thread::sleep(POLL);
+        }
+    }
+    /// restrict applies the CPU and memory limits to the child process.
+    #[cfg(target_os = "linux")]
+    fn restrict(&self, cmd: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let (cpu, memory) = (self.cpu_seconds, self.memory_bytes);
+        if cpu.is_none() && memory.is_none() {
+            return;
+        }
+        let limits = move || {
+            if let Some(secs) = cpu {
+                // a second's grace past the soft limit, so that ec is sent
+                // SIGXCPU rather than SIGKILL.
+                if unsafe { libc::setrlimit(libc::RLIMIT_CPU, &rlimit(secs, secs + 1)) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            if let Some(bytes) = memory {
+                if unsafe { libc::setrlimit(libc::RLIMIT_AS, &rlimit(bytes, bytes)) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        };
+        // only async-signal-safe calls are made between fork and exec.
+        unsafe {
+            cmd.pre_exec(limits);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    fn restrict(&self, _cmd: &mut Command) {
+        if self.cpu_seconds.is_some() || self.memory_bytes.is_some() {
+            eprintln!("ec resource limits are only supported on Linux; ignoring them");
+        }
+    }
+}
+
+/// rlimit makes a soft and a hard limit.
+#[cfg(target_os = "linux")]
+fn rlimit(soft: u64, hard: u64) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    }
+}
+
+/// kill kills ec along with anything it started, which share its process
+/// group if it has a timeout.
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// Drain reads a pipe to its end on another thread.
+struct Drain {
+    buf: Arc<Mutex<Vec<u8>>>,
+    done: mpsc::Receiver<()>,
+}
+
+impl Drain {
+    fn new<R>(pipe: Option<R>) -> Drain
+        where R: Read + Send + 'static
+    {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let (tx, done) = mpsc::channel();
+        let read = buf.clone();
+        thread::spawn(move || {
+            if let Some(mut pipe) = pipe {
+                let mut chunk = [0u8; 8192];
+                loop {
+                    match pipe.read(&mut chunk) {
+                        Ok(0) => break,
+                        Ok(n) => read.lock().unwrap().extend_from_slice(&chunk[..n]),
+                        Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                        Err(_) => break,
+                    }
+                }
+            }
+            let _ = tx.send(());
+        });
+        Drain { buf, done }
+    }
+    /// finish gives what has been read once the pipe has ended, or once
+    /// the grace has passed if it hasn't. In that case the thread is left
+    /// to end with the pipe.
+    fn finish(self, grace: Duration) -> Vec<u8> {
+        let _ = self.done.recv_timeout(grace);
+        let mut buf = self.buf.lock().unwrap();
+        mem::take(&mut *buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use tempdir::TempDir;
+
+    use super::Limits;
+    use super::super::EcError;
+
+    #[test]
+    fn ec_limits() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(200)),
+            retries: 1,
+            backoff: Duration::from_millis(10),
+            ..Limits::default()
+        };
+        let start = Instant::now();
+        match limits.run("sleep", "10", 1) {
+            Err(EcError::Timeout { phase: 1, .. }) => {}
+            other => panic!("expected a timeout, got {:?}", other.err()),
+        }
+        // killed on each of the two tries, rather than left to finish.
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        match limits.run("context-no-such-ec", "input.json", 2) {
+            Err(EcError::MissingBinary(_)) => {}
+            other => panic!("expected a missing binary, got {:?}", other.err()),
+        }
+        let out = limits.run("echo", "results", 3).unwrap();
+        assert_eq!(out.stdout, b"results\n");
+    }
+
+    #[test]
+    fn ec_retries() {
+        let tmp_dir = TempDir::new("limits").unwrap();
+        let script = |name: &str, body: &str| {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, body).unwrap();
+            String::from(path.to_str().unwrap())
+        };
+        let runs = tmp_dir.path().join("runs");
+        let failing = script("failing", &format!("echo run >> {}\nexit 3\n", runs.display()));
+        let limits = Limits {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            ..Limits::default()
+        };
+        match limits.run("sh", &failing, 1) {
+            Err(EcError::Exit { phase: 1, code: Some(3), .. }) => {}
+            other => panic!("expected an exit, got {:?}", other.err()),
+        }
+        // one run and two retries, ending in a single error.
+        assert_eq!(fs::read_to_string(&runs).unwrap().lines().count(), 3);
+
+        if cfg!(target_os = "linux") {
+            let spinning = script("spinning", "while :; do :; done\n");
+            let limits = Limits {
+                cpu_seconds: Some(1),
+                ..Limits::default()
+            };
+            let err = limits.run("sh", &spinning, 2).err().unwrap();
+            match err {
+                EcError::CpuLimit { phase: 2, seconds: 1 } => {}
+                ref other => panic!("expected the CPU limit, got {:?}", other),
+            }
+            assert_eq!(err.to_string(), "ec exceeded its CPU limit of 1s in phase 2");
+
+            let starved = script("starved", "echo 'Fatal error: exception Out_of_memory' >&2\nexit 2\n");
+            let limits = Limits {
+                memory_bytes: Some(512 << 20),
+                ..Limits::default()
+            };
+            let err = limits.run("sh", &starved, 3).err().unwrap();
+            assert_eq!(err.to_string(),
+                       "ec ran out of memory in phase 3, under its limit of 512 MB: \
+                        Fatal error: exception Out_of_memory");
+            // a crash that doesn't say it's from memory isn't put down to it.
+            let crashing = script("crashing", "echo 'could not allocate a grammar' >&2\nkill -ABRT $$\n");
+            match limits.run("sh", &crashing, 3) {
+                Err(EcError::Exit { signal: Some(6), .. }) => {}
+                other => panic!("expected an exit, got {:?}", other.err()),
+            }
+            // without a memory limit, the same failure is only an exit.
+            match Limits::default().run("sh", &starved, 3) {
+                Err(EcError::Exit { code: Some(2), .. }) => {}
+                other => panic!("expected an exit, got {:?}", other.err()),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn ec_leftovers() {
+        let tmp_dir = TempDir::new("limits").unwrap();
+        let pid_file = tmp_dir.path().join("pid");
+        let script = |name: &str, body: &str| {
+            let path = tmp_dir.path().join(name);
+            fs::write(&path, body).unwrap();
+            String::from(path.to_str().unwrap())
+        };
+        // ec exits, but leaves something running that holds its output
+        // open: what it wrote is given anyway.
+        let leaving = script("leaving", "sleep 30 &\necho done\n");
+        let start = Instant::now();
+        let out = Limits::default().run("sh", &leaving, 1).unwrap();
+        assert_eq!(out.stdout, b"done\n");
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // ec times out, and what it started is killed with it.
+        let waiting = script("waiting", &format!("sleep 30 &\necho $! > {}\nwait\n", pid_file.display()));
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(500)),
+            ..Limits::default()
+        };
+        let start = Instant::now();
+        match limits.run("sh", &waiting, 2) {
+            Err(EcError::Timeout { phase: 2, .. }) => {}
+            other => panic!("expected a timeout, got {:?}", other.err()),
+        }
+        assert!(start.elapsed() < Duration::from_secs(10));
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        // gone, or dead and waiting on whatever reaps orphans.
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+        assert!(stat.is_empty() || stat.contains(") Z "), "left running: {}", stat);
+    }
+}
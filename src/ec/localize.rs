@@ -0,0 +1,260 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use knowledge::{Context, Mechanism};
+use super::{Combinators, LOG_LEVEL};
+use super::course::{Course, default_curriculum};
+
+type Features = BTreeMap<String, f64>;
+
+/// Localizer is a mechanism that orients the knowledge network to the item
+/// that suits the upcoming course, so that the curriculum might not need to
+/// be ordered by hand (curriculum/ec_localize is for measuring whether it
+/// does). It must step before the ec mechanism, so should be given a
+/// higher priority.
+///
+/// It is a multiclass perceptron from the features of a course to an item
+/// id. After ec has run on a course, the item that ec oriented to is taken
+/// as the right answer for that course, and is learned from at the end of
+/// the iteration: that is where the network was left, or, if ec grew an
+/// item, where it was centered when it grew it, since a new item was never
+/// there to be oriented to. It can only orient to items it has learned
+/// from, so a course unlike any seen before gets whatever context ec last
+/// left, and an item that has been retired is forgotten.
+#[derive(Debug, Default)]
+pub struct Localizer {
+    /// weights maps an item id to the weight of each feature for that item.
+    weights: BTreeMap<usize, Features>,
+    /// the features of the course that ec is given in this iteration, to be
+    /// learned from once it is over.
+    pending: Option<Features>,
+    curriculum: Option<String>,
+}
+
+impl Localizer {
+    pub fn new() -> Localizer {
+        Localizer::default()
+    }
+    /// `use_curriculum` makes the courses be read from the given directory,
+    /// rather than that given by `$EC_CURRICULUM`, as for `Ec`.
+    pub fn use_curriculum(&mut self, dir: &str) {
+        self.curriculum = Some(String::from(dir));
+    }
+    /// predict gives the item with the highest score for the features, or
+    /// None if nothing has been learned. Ties go to the oldest item.
+    fn predict(&self, x: &Features) -> Option<usize> {
+        let mut best: Option<(usize, f64)> = None;
+        for (&id, w) in &self.weights {
+            let score = dot(w, x);
+            if best.map_or(true, |(_, s)| score > s) {
+                best = Some((id, score));
+            }
+        }
+        best.map(|(id, _)| id)
+    }
+    /// learn makes the perceptron update towards target for features that
+    /// it mispredicts.
+    fn learn(&mut self, x: &Features, target: usize) {
+        let predicted = self.predict(x);
+        if predicted == Some(target) {
+            return;
+        }
+        add(self.weights.entry(target).or_default(), x, 1f64);
+        if let Some(id) = predicted {
+            add(self.weights.get_mut(&id).unwrap(), x, -1f64);
+        }
+    }
+}
+
+impl Mechanism<Combinators> for Localizer {
+    fn step(&mut self, ctx: Context<Combinators>, i: u64) -> Result<(), Box<dyn Error>> {
+        let curriculum = self.curriculum.clone().map_or_else(default_curriculum, Ok)?;
+        let x = Course::load(&curriculum, i)?.features();
+        while let Some(id) = self.predict(&x) {
+            if ctx.provenance(id).is_none() {
+                eprintln!("localize: forgetting item {}, which has been retired", id);
+                self.weights.remove(&id);
+                continue;
+            }
+            if id != ctx.origin() {
+                if LOG_LEVEL & 2 != 0 {
+                    println!("   localize: ctx.orient({})", id);
+                }
+                ctx.orient(id);
+            }
+            break;
+        }
+        self.pending = Some(x);
+        Ok(())
+    }
+    fn after_iteration(&mut self, ctx: Context<Combinators>, i: u64) {
+        if let Some(x) = self.pending.take() {
+            self.learn(&x, target(&ctx, i));
+        }
+    }
+    /// save gives the weights. Nothing is pending between iterations.
+    fn save(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(&self.weights).ok()
+    }
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        self.weights = serde_json::from_value(state)?;
+        Ok(())
+    }
+}
+
+/// target gives the item that a course of this iteration is learned as:
+/// where the network was left, unless that was grown in this iteration and
+/// so could never have been oriented to, in which case the item it was
+/// grown from.
+fn target(ctx: &Context<Combinators>, i: u64) -> usize {
+    let mut id = ctx.origin();
+    while ctx.provenance(id).is_some_and(|p| p.iteration == i) {
+        match ctx.grown_from(id) {
+            Some(from) => id = from,
+            None => break,
+        }
+    }
+    id
+}
+
+fn dot(w: &Features, x: &Features) -> f64 {
+    x.iter()
+        .map(|(f, v)| w.get(f).unwrap_or(&0f64) * v)
+        .sum()
+}
+
+fn add(w: &mut Features, x: &Features, scale: f64) {
+    for (f, v) in x {
+        *w.entry(f.clone()).or_insert(0f64) += scale * v;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use knowledge::{Context, Mechanism, Schedule, Skn};
+    use super::{Features, Localizer, target};
+    use super::super::Combinators;
+    use super::super::course::Course;
+
+    fn course(name: &str, i: &str, o: &str) -> Course {
+        let json = format!(r#"{{"tasks": [{{"name": "{}", "train": [{{"i": "{}", "o": "{}"}}], "test": []}}],
+                               "grammar": []}}"#,
+                           name,
+                           i,
+                           o);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn course_features() {
+        let x = course("Add Dots", "ab", "a.b").features();
+        let expected: Vec<(&str, f64)> = vec![("bias", 1f64),
+                                              ("i_digit", 0f64),
+                                              ("i_len", 0.2),
+                                              ("i_lower", 1f64),
+                                              ("i_punct", 0f64),
+                                              ("i_space", 0f64),
+                                              ("i_upper", 0f64),
+                                              ("len_ratio", 4f64 / 3f64),
+                                              ("name:add", 1f64),
+                                              ("name:dots", 1f64),
+                                              ("o_digit", 0f64),
+                                              ("o_in_i", 0f64),
+                                              ("o_len", 0.3),
+                                              ("o_lower", 2f64 / 3f64),
+                                              ("o_punct", 1f64 / 3f64),
+                                              ("o_space", 0f64),
+                                              ("o_upper", 0f64)];
+        let got: Vec<(&str, f64)> = x.iter().map(|(f, &v)| (f.as_str(), v)).collect();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn perceptron() {
+        let dots = course("add dots", "ab", "a.b").features();
+        let caps = course("capitalize", "abc def", "Abc Def").features();
+        let mut localizer = Localizer::new();
+        assert_eq!(localizer.predict(&dots), None);
+
+        localizer.learn(&dots, 3);
+        assert_eq!(localizer.predict(&dots), Some(3));
+        assert_eq!(localizer.predict(&caps), Some(3));
+
+        // a misprediction moves weight from the predicted item to the
+        // target, until each course is told apart.
+        for _ in 0..10 {
+            localizer.learn(&dots, 3);
+            localizer.learn(&caps, 5);
+        }
+        assert_eq!(localizer.predict(&dots), Some(3));
+        assert_eq!(localizer.predict(&caps), Some(5));
+
+        // learning what is already predicted changes nothing.
+        let weights = localizer.weights.clone();
+        localizer.learn(&caps, 5);
+        assert_eq!(localizer.weights, weights);
+
+        // what is learned survives a checkpoint.
+        let mut restored = Localizer::new();
+        restored.restore(localizer.save().unwrap()).unwrap();
+        assert_eq!(restored.weights, localizer.weights);
+
+        // ties go to the oldest item.
+        let mut tied = Localizer::new();
+        let x: Features = vec![(String::from("bias"), 1f64)].into_iter().collect();
+        tied.weights.insert(7, x.clone());
+        tied.weights.insert(2, x.clone());
+        assert_eq!(tied.predict(&x), Some(2));
+    }
+
+    #[test]
+    fn localized_run() {
+        let embryo = vec![("ec", Combinators(vec![])), ("ec", Combinators(vec![]))];
+        let mut skn = Skn::with_seed(embryo, 1, 7);
+        // the best prediction is an item that isn't in the network, which
+        // is forgotten in favor of the next best.
+        let mut localizer = Localizer::new();
+        localizer.use_curriculum("curriculum/ec_reiter");
+        let bias = |w: f64| -> Features { vec![(String::from("bias"), w)].into_iter().collect() };
+        localizer.weights.insert(9, bias(2f64));
+        localizer.weights.insert(1, bias(1f64));
+        let first = Schedule {
+            priority: 1,
+            ..Schedule::default()
+        };
+        skn.register_with_schedule("localize", localizer, first);
+        let ec = |ctx: Context<Combinators>, i: u64| {
+            assert_eq!(ctx.origin(), 1);
+            ctx.orient(0);
+            let grown = ctx.grow(Combinators(vec![format!("{}", i)]));
+            // a course is learned as where the network was before growing.
+            let ctx = ctx.update();
+            assert_eq!(ctx.origin(), grown);
+            assert_eq!(target(&ctx, i), 0);
+        };
+        skn.register("ec", ec);
+        skn.run();
+    }
+}
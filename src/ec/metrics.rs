@@ -0,0 +1,228 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+
+extern crate serde;
+extern crate serde_json;
+
+use std::fmt;
+use std::io::{self, Write};
+
+use self::serde::Serialize;
+
+use knowledge::strategy::Ablation;
+use super::EcError;
+use super::results::Task;
+
+/// Metrics writes what ec did in each iteration, so that runs can be
+/// compared and plotted without rerunning ec afterward.
+///
+/// The TSV has a row for every solved task, with the columns task, time,
+/// log probability, seconds taken by the ec run, expression, and
+/// iteration. These are the columns that `context plot` reads. The seconds
+/// are wall-clock time, whereas the TSVs once made by
+/// `results/produce_data.sh` had the user CPU time of ec (`/bin/time -f
+/// %U`) in that column, so the two shouldn't be plotted together.
+///
+/// The JSON Lines have a line for every iteration, with the hit rate,
+/// failed tasks, each task's result, and the sizes of the context, the
+/// network, and the grammar given to ec. An iteration in which ec itself
+/// failed, e.g. by running out of time, has a line with only the
+/// iteration and the error. Metrics are also written for the
+/// baselines of a comparison, which run ec outside of any network.
+#[derive(Default)]
+pub struct Metrics {
+    tsv: Option<Box<dyn Write>>,
+    jsonl: Option<Box<dyn Write>>,
+}
+
+impl fmt::Debug for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Metrics")
+    }
+}
+
+/// Iteration is a single line of the JSON Lines metrics.
+#[derive(Serialize)]
+pub struct Iteration<'a> {
+    pub iteration: u64,
+    pub hit_rate: u64,
+    pub failed: Vec<&'a str>,
+    pub tasks: &'a [Task],
+    /// the number of items in the context, or None if ec wasn't run in a
+    /// knowledge network.
+    pub context_size: Option<usize>,
+    /// the number of items in the knowledge network, if any.
+    pub network_size: Option<usize>,
+    pub grammar_size: usize,
+    /// the wall-clock time taken by the ec run.
+    pub seconds: f64,
+    /// the Ablation, if any, of the context that ec was given.
+    pub ablation: Option<Ablation>,
+}
+
+/// Failure is a line of the JSON Lines metrics for an iteration in which
+/// ec could not be run to completion.
+#[derive(Serialize)]
+struct Failure<'a> {
+    iteration: u64,
+    error: &'a str,
+}
+
+impl Metrics {
+    pub fn tsv_to(&mut self, w: Box<dyn Write>) {
+        self.tsv = Some(w);
+    }
+    pub fn jsonl_to(&mut self, w: Box<dyn Write>) {
+        self.jsonl = Some(w);
+    }
+    /// record writes the metrics of an iteration. Failures are reported but
+    /// don't interrupt ec.
+    pub fn record(&mut self, it: &Iteration) {
+        if let Some(ref mut w) = self.tsv {
+            if let Err(err) = write_tsv(w, it) {
+                eprintln!("could not write metrics tsv in phase {}: {}", it.iteration, err)
+            }
+        }
+        if let Some(ref mut w) = self.jsonl {
+            if let Err(err) = write_jsonl(w, it) {
+                eprintln!("could not write metrics jsonl in phase {}: {}", it.iteration, err)
+            }
+        }
+    }
+    /// record_failure writes that ec failed in an iteration, with the
+    /// reason. Nothing is written to the TSV, which only has solved tasks.
+    pub fn record_failure(&mut self, iteration: u64, err: &EcError) {
+        if let Some(ref mut w) = self.jsonl {
+            let error = err.to_string();
+            let failure = Failure {
+                iteration,
+                error: &error,
+            };
+            if let Err(err) = write_jsonl(w, &failure) {
+                eprintln!("could not write metrics jsonl in phase {}: {}", iteration, err)
+            }
+        }
+    }
+}
+
+fn write_tsv(w: &mut Box<dyn Write>, it: &Iteration) -> io::Result<()> {
+    for task in it.tasks {
+        if let Some(ref r) = task.result {
+            writeln!(w,
+                     "{}\t{}\t{}\t{}\t{}\t{}",
+                     task.task,
+                     r.time,
+                     r.log_probability,
+                     it.seconds,
+                     r.expr,
+                     it.iteration)?;
+        }
+    }
+    w.flush()
+}
+
+fn write_jsonl<S: Serialize>(w: &mut Box<dyn Write>, it: &S) -> io::Result<()> {
+    serde_json::to_writer(&mut *w, it)?;
+    writeln!(w)?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use std::cell::RefCell;
+    use std::io::{self, Write};
+    use std::rc::Rc;
+
+    use knowledge::strategy::Ablation;
+    use super::{Iteration, Metrics};
+    use super::super::EcError;
+    use super::super::results::{Task, TaskResult};
+
+    /// Shared is an in-memory writer that can be read after it is given
+    /// away.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.borrow().clone()).unwrap().lines().map(String::from).collect()
+        }
+    }
+
+    #[test]
+    fn record_iterations() {
+        let (tsv, jsonl) = (Shared::default(), Shared::default());
+        let mut metrics = Metrics::default();
+        metrics.tsv_to(Box::new(tsv.clone()));
+        metrics.jsonl_to(Box::new(jsonl.clone()));
+        let tasks = vec![Task {
+                             task: String::from("nospaces"),
+                             result: Some(TaskResult {
+                                              expr: String::from("(replace <SPACE>)"),
+                                              log_probability: -3.5,
+                                              time: 0.25,
+                                          }),
+                         },
+                         Task {
+                             task: String::from("SICP"),
+                             result: None,
+                         }];
+        metrics.record(&Iteration {
+            iteration: 2,
+            hit_rate: 1,
+            failed: vec!["SICP"],
+            tasks: &tasks,
+            context_size: Some(3),
+            network_size: Some(7),
+            grammar_size: 40,
+            seconds: 1.5,
+            ablation: Some(Ablation::Random),
+        });
+        metrics.record_failure(3, &EcError::MissingCurriculum);
+
+        // only solved tasks have a row, and failures don't.
+        assert_eq!(tsv.lines(), vec!["nospaces\t0.25\t-3.5\t1.5\t(replace <SPACE>)\t2"]);
+
+        let lines: Vec<serde_json::Value> =
+            jsonl.lines().iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["iteration"], 2);
+        assert_eq!(lines[0]["hit_rate"], 1);
+        assert_eq!(lines[0]["failed"], serde_json::json!(["SICP"]));
+        assert_eq!(lines[0]["tasks"][0]["result"]["time"], 0.25);
+        assert_eq!(lines[0]["tasks"][1]["result"], serde_json::Value::Null);
+        assert_eq!(lines[0]["context_size"], 3);
+        assert_eq!(lines[0]["network_size"], 7);
+        assert_eq!(lines[0]["grammar_size"], 40);
+        assert_eq!(lines[0]["seconds"], 1.5);
+        assert_eq!(lines[0]["ablation"], "random");
+        assert_eq!(lines[1], serde_json::json!({"iteration": 3, "error": "could not find ec curriculum"}));
+    }
+}
@@ -0,0 +1,325 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+
+extern crate rand;
+extern crate serde_json;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use knowledge::Skn;
+use super::{Ec, EcError, Limits, LOG_LEVEL, embryo, iter_max};
+use super::results::Task;
+
+/// two-sided 95% critical values of Student's t distribution, by degrees
+/// of freedom from 1. Beyond these, the normal distribution's is used.
+static T95: [f64; 30] = [12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+                         2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+                         2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042];
+const Z95: f64 = 1.960;
+
+/// Trials runs the ec mechanism in a knowledge network over the same
+/// curriculum many times, each with a distinct seed, because a single run
+/// says little when the network grows stochastically.
+///
+/// Each trial is written to its own directory in out, `trial_<n>`, with
+/// the metrics of ec (see `Ec::metrics_tsv_to` and `Ec::metrics_jsonl_to`)
+/// and a snapshot of the final network. The trials are then aggregated into
+/// summary.json.
+#[derive(Clone, Debug)]
+pub struct Trials {
+    /// the curriculum directory, like curriculum/ec.
+    pub curriculum: String,
+    /// the ec binary, like that given by `ec_bin`.
+    pub ec: String,
+    /// the number of trials.
+    pub trials: u64,
+    /// the seed of the first trial. The nth trial is seeded with seed + n.
+    /// If None, a seed is chosen randomly.
+    pub seed: Option<u64>,
+    /// the directory that results are written to.
+    pub out: String,
+    /// the Limits of each run of ec.
+    pub limits: Limits,
+}
+
+/// Summary is the distribution of a quantity over trials.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Summary {
+    /// the number of trials the quantity was measured in.
+    pub n: usize,
+    pub mean: f64,
+    /// the sample standard deviation, if n > 1.
+    pub std: Option<f64>,
+    /// the 95% confidence interval of the mean, if n > 1.
+    pub ci95: Option<(f64, f64)>,
+}
+
+impl Summary {
+    /// of summarizes some measurements, or gives None if there are none.
+    pub fn of(xs: &[f64]) -> Option<Summary> {
+        if xs.is_empty() {
+            return None;
+        }
+        let n = xs.len();
+        let mean = xs.iter().sum::<f64>() / n as f64;
+        if n == 1 {
+            return Some(Summary {
+                            n,
+                            mean,
+                            std: None,
+                            ci95: None,
+                        });
+        }
+        let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        let std = var.sqrt();
+        let t = T95.get(n - 2).cloned().unwrap_or(Z95);
+        let half = t * std / (n as f64).sqrt();
+        Some(Summary {
+                 n,
+                 mean,
+                 std: Some(std),
+                 ci95: Some((mean - half, mean + half)),
+             })
+    }
+}
+
+/// Record is what is read back from a line of the JSON Lines metrics. A
+/// line for an iteration in which ec failed has only the error.
+#[derive(Deserialize)]
+struct Record {
+    iteration: u64,
+    #[serde(default)]
+    hit_rate: u64,
+    #[serde(default)]
+    tasks: Vec<Task>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// TaskSummary aggregates a task in an iteration over the trials that
+/// solved it.
+#[derive(Serialize)]
+struct TaskSummary {
+    task: String,
+    /// the number of trials that solved the task.
+    solved: usize,
+    time: Option<Summary>,
+    log_probability: Option<Summary>,
+}
+
+/// IterationSummary aggregates an iteration over every trial.
+#[derive(Serialize)]
+struct IterationSummary {
+    iteration: u64,
+    /// the number of trials in which ec failed in this iteration, which
+    /// are left out of the hit rate and tasks.
+    failed: usize,
+    hit_rate: Option<Summary>,
+    tasks: Vec<TaskSummary>,
+}
+
+/// TrialsSummary is written to summary.json.
+#[derive(Serialize)]
+struct TrialsSummary {
+    trials: u64,
+    seeds: Vec<u64>,
+    iterations: Vec<IterationSummary>,
+}
+
+impl Trials {
+    /// run performs every trial and writes the results. Failed iterations
+    /// of a trial are reported and left out.
+    pub fn run(&self) -> Result<(), EcError> {
+        let base = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let seeds: Vec<u64> = (0..self.trials).map(|n| base.wrapping_add(n)).collect();
+        for (n, &seed) in seeds.iter().enumerate() {
+            if LOG_LEVEL & 1 != 0 {
+                println!("trial {} of {} with seed {}", n + 1, self.trials, seed);
+            }
+            self.trial(n, seed)?;
+        }
+        let records = (0..seeds.len()).map(|n| self.read(n)).collect::<Result<Vec<_>, _>>()?;
+        let summary = TrialsSummary {
+            trials: self.trials,
+            seeds,
+            iterations: aggregate(&records),
+        };
+        let path = Path::new(&self.out).join("summary.json");
+        let f = File::create(path)?;
+        serde_json::to_writer_pretty(f, &summary).map_err(|err| EcError::json("summary", err))
+    }
+    fn dir(&self, n: usize) -> PathBuf {
+        Path::new(&self.out).join(format!("trial_{:02}", n + 1))
+    }
+    /// trial runs a single trial into its own directory.
+    fn trial(&self, n: usize, seed: u64) -> Result<(), EcError> {
+        let dir = self.dir(n);
+        fs::create_dir_all(&dir)?;
+        let mut skn = Skn::with_seed(embryo(&self.curriculum)?, iter_max(&self.curriculum)?, seed);
+        let mut ec = Ec::new();
+        ec.use_curriculum(&self.curriculum);
+        ec.use_bin(&self.ec);
+        ec.store_inputs(false);
+        ec.set_limits(self.limits.clone());
+        ec.metrics_tsv_to(File::create(dir.join("metrics.tsv"))?);
+        ec.metrics_jsonl_to(File::create(dir.join("metrics.jsonl"))?);
+        skn.register("ec", ec);
+        skn.run();
+        let mut f = File::create(dir.join("network.json"))?;
+        skn.save(&mut f).map_err(|err| EcError::json("snapshot", err))
+    }
+    /// read reads back the metrics of a trial.
+    fn read(&self, n: usize) -> Result<Vec<Record>, EcError> {
+        let f = File::open(self.dir(n).join("metrics.jsonl"))?;
+        let mut records = Vec::new();
+        for line in BufReader::new(f).lines() {
+            records.push(serde_json::from_str(&line?).map_err(|err| EcError::Parse("metrics", err))?);
+        }
+        Ok(records)
+    }
+}
+
+/// Solutions maps a task to the time and log probability of its solution in
+/// each trial that solved it.
+type Solutions<'a> = BTreeMap<&'a str, Vec<(f64, f64)>>;
+
+/// aggregate summarizes, for each iteration, the hit rate and the time and
+/// log probability of each task over the trials in which ec ran, and
+/// counts the trials in which it failed.
+fn aggregate(trials: &[Vec<Record>]) -> Vec<IterationSummary> {
+    let mut hit_rates: BTreeMap<u64, Vec<f64>> = BTreeMap::new();
+    let mut failed: BTreeMap<u64, usize> = BTreeMap::new();
+    let mut solutions: BTreeMap<u64, Solutions> = BTreeMap::new();
+    for record in trials.iter().flat_map(|t| t.iter()) {
+        if record.error.is_some() {
+            *failed.entry(record.iteration).or_default() += 1;
+            hit_rates.entry(record.iteration).or_default();
+            continue;
+        }
+        hit_rates.entry(record.iteration).or_default().push(record.hit_rate as f64);
+        let tasks = solutions.entry(record.iteration).or_default();
+        for task in &record.tasks {
+            let solved = tasks.entry(task.task.as_str()).or_default();
+            if let Some(ref r) = task.result {
+                solved.push((r.time, r.log_probability));
+            }
+        }
+    }
+    hit_rates
+        .into_iter()
+        .map(|(iteration, hit_rate)| {
+            let tasks = solutions
+                .remove(&iteration)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(task, solved)| {
+                         let times: Vec<f64> = solved.iter().map(|s| s.0).collect();
+                         let log_probabilities: Vec<f64> = solved.iter().map(|s| s.1).collect();
+                         TaskSummary {
+                             task: String::from(task),
+                             solved: solved.len(),
+                             time: Summary::of(&times),
+                             log_probability: Summary::of(&log_probabilities),
+                         }
+                     })
+                .collect();
+            IterationSummary {
+                iteration,
+                failed: failed.get(&iteration).cloned().unwrap_or_default(),
+                hit_rate: Summary::of(&hit_rate),
+                tasks,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::{Record, Summary, aggregate};
+
+    #[test]
+    fn trial_summaries() {
+        assert_eq!(Summary::of(&[]), None);
+        let one = Summary::of(&[3f64]).unwrap();
+        assert_eq!((one.n, one.mean, one.std, one.ci95), (1, 3f64, None, None));
+
+        let s = Summary::of(&[2f64, 4f64, 6f64]).unwrap();
+        assert_eq!(s.n, 3);
+        assert_eq!(s.mean, 4f64);
+        assert_eq!(s.std, Some(2f64));
+        // t = 4.303 with two degrees of freedom.
+        let (lo, hi) = s.ci95.unwrap();
+        let half = 4.303 * 2f64 / 3f64.sqrt();
+        assert!((lo - (4f64 - half)).abs() < 1e-9);
+        assert!((hi - (4f64 + half)).abs() < 1e-9);
+    }
+
+    /// record reads a line of metrics, as written by Metrics.
+    fn record(line: &str) -> Record {
+        serde_json::from_str(line).unwrap()
+    }
+
+    #[test]
+    fn aggregate_trials() {
+        let solved = |iteration: u64, time: f64| {
+            record(&format!(r#"{{"iteration": {}, "hit_rate": 1, "failed": ["SICP"], "grammar_size": 4,
+                                  "tasks": [{{"task": "nospaces",
+                                              "result": {{"expr": "e", "log_probability": -2.0, "time": {}}}}},
+                                             {{"task": "SICP", "result": null}}]}}"#,
+                            iteration,
+                            time))
+        };
+        let unsolved = |iteration: u64| {
+            record(&format!(r#"{{"iteration": {}, "hit_rate": 0,
+                                  "tasks": [{{"task": "nospaces", "result": null}}]}}"#,
+                            iteration))
+        };
+        let failed = |iteration: u64| record(&format!(r#"{{"iteration": {}, "error": "ec timed out"}}"#, iteration));
+        let trials = vec![vec![solved(1, 1f64), solved(2, 2f64)],
+                          vec![solved(1, 3f64), failed(2)],
+                          vec![unsolved(1), failed(2)]];
+        let summary = aggregate(&trials);
+        assert_eq!(summary.len(), 2);
+
+        let first = &summary[0];
+        assert_eq!((first.iteration, first.failed), (1, 0));
+        let hit_rate = first.hit_rate.as_ref().unwrap();
+        assert_eq!((hit_rate.n, hit_rate.mean), (3, 2f64 / 3f64));
+        let tasks: Vec<(&str, usize)> = first.tasks.iter().map(|t| (t.task.as_str(), t.solved)).collect();
+        assert_eq!(tasks, vec![("SICP", 0), ("nospaces", 2)]);
+        let time = first.tasks[1].time.as_ref().unwrap();
+        assert_eq!((time.n, time.mean), (2, 2f64));
+        assert!(first.tasks[0].time.is_none());
+
+        // the trials that failed are counted rather than taken as solving
+        // nothing.
+        let second = &summary[1];
+        assert_eq!((second.iteration, second.failed), (2, 2));
+        let hit_rate = second.hit_rate.as_ref().unwrap();
+        assert_eq!((hit_rate.n, hit_rate.mean), (1, 1f64));
+        assert_eq!(second.tasks[1].solved, 1);
+    }
+}
@@ -16,45 +16,133 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 extern crate rand;
+extern crate serde;
+extern crate serde_json;
 
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
 use std::cmp::{min, max};
-use std::collections::{HashSet, HashMap};
-use std::cell::RefCell;
+use std::collections::{BTreeSet, BTreeMap, VecDeque};
+use std::collections::btree_map::Entry;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
 use std::fmt;
+use std::mem;
 use std::rc::Rc;
+use std::time::Instant;
 
-use rand::distributions::{IndependentSample, Range};
+use rand::{Rng, SeedableRng, StdRng};
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+
+/// snapshot is for saving and restoring an entire knowledge network.
+mod snapshot;
+pub use self::snapshot::{Snapshot, Checkpoint, LoadError};
+/// strategy is for the ways that a knowledge network can be shaped.
+pub mod strategy;
+use self::strategy::{ContextStrategy, AttachmentStrategy, Attachment, Ablation, Decay, NetView};
+/// evict is for retiring items when the network is full.
+mod evict;
+pub use self::evict::Eviction;
+/// journal is for recording and replaying mutations of a knowledge network.
+mod journal;
+pub use self::journal::Event;
+use self::journal::Journal;
+/// observe is for watching what happens to a knowledge network.
+mod observe;
+pub use self::observe::Observer;
+use self::observe::Observers;
+/// schedule is for deciding when each mechanism steps and when to stop.
+mod schedule;
+pub use self::schedule::{OnFailure, Schedule, Stop};
+/// stats is for measuring the shape of a knowledge network.
+pub mod stats;
+use self::stats::Stats;
 
 const CTX_MIN_SIZE: usize = 3;
 const NET_MAX_SIZE: usize = 128;
+/// the amount an edge's weight increases for each epoch in which both of
+/// its items are accessed.
+const EDGE_REINFORCE: f64 = 1.0;
+/// the proportion of an edge's excess weight (over the base weight of 1)
+/// that remains after an epoch in which its items are not both accessed.
+const EDGE_DECAY: f64 = 0.9;
+
+/// Payload is the type of data held by the items of a knowledge network.
+/// Payloads are saved, journaled and restored with serde, so malformed
+/// data is caught when it is read rather than when a mechanism uses it.
+pub trait Payload: Serialize + DeserializeOwned + fmt::Debug + 'static {}
+
+impl<T> Payload for T where T: Serialize + DeserializeOwned + fmt::Debug + 'static {}
 
-/// Item maintains the data and metadata for a single knowledge artifact.
+/// Item maintains the metadata for a single knowledge artifact. Its data
+/// is kept alongside, in the Net.
 #[derive(Debug)]
 struct Item {
     /// mechanism name
     mech: &'static str,
-    /// arbitrary data
-    data: Rc<String>,
     /// counts maps an epoch to a number of accesses to this artifact made
     /// during that epoch.
-    counts: HashMap<usize, u64>,
+    counts: BTreeMap<usize, u64>,
     /// adj is the set of adjacent item ids.
-    adj: HashSet<usize>,
+    adj: BTreeSet<usize>,
+    /// weights maps adjacent item ids to the weight of their edge, for
+    /// those edges whose weight has moved from the base weight of 1.
+    weights: BTreeMap<usize, f64>,
     /// id is this item's unique identifier.
     id: usize,
+    /// provenance records how this item came to be in the network.
+    provenance: Provenance,
+}
+
+/// Provenance records how an item came to be in the network.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provenance {
+    /// the mechanism whose Context grew the item.
+    pub mech: &'static str,
+    /// the iteration in which the item was grown, or 0 for the embryo.
+    pub iteration: u64,
+    /// the epoch that the item was born in.
+    pub epoch: usize,
+    /// the ids of the items that the item was first adjacent to.
+    pub parents: BTreeSet<usize>,
+    /// the ids of the items that were considered as the item's neighbors,
+    /// with the probability that each would be chosen.
+    pub antecedents: BTreeMap<usize, f64>,
 }
 
 impl Item {
-    fn new(mech: &'static str, adj: HashSet<usize>, data: String, id: usize) -> Item {
+    /// new creates an item with the provenance of an embryo item.
+    fn new(mech: &'static str, adj: BTreeSet<usize>, id: usize) -> Item {
         Item {
-            mech: mech,
-            data: Rc::new(data),
-            counts: HashMap::new(),
-            adj: adj,
-            id: id,
+            mech,
+            counts: BTreeMap::new(),
+            adj,
+            weights: BTreeMap::new(),
+            id,
+            provenance: Provenance {
+                mech,
+                iteration: 0,
+                epoch: 0,
+                parents: BTreeSet::new(),
+                antecedents: BTreeMap::new(),
+            },
         }
     }
+    /// link adds an edge to another item.
+    fn link(&mut self, oid: usize) {
+        self.adj.insert(oid);
+    }
+    /// unlink removes an edge to another item.
+    fn unlink(&mut self, oid: usize) {
+        self.adj.remove(&oid);
+        self.weights.remove(&oid);
+    }
+    /// weight gives the weight of the edge to an adjacent item.
+    fn weight(&self, oid: usize) -> f64 {
+        *self.weights.get(&oid).unwrap_or(&1f64)
+    }
     /// increases this item's access count for a given epoch.
     fn add_count(&mut self, epoch: usize, count: u64) {
         let prev_count: u64 = {
@@ -73,84 +161,263 @@ impl Item {
 
 /// Context is the interface for mechanisms to utilize the knowledge
 /// network.
-pub struct Context {
+pub struct Context<T = String> {
     /// net is the network that the context corresponds to.
-    net: Network,
+    net: Network<T>,
     /// name of the mechanism that's using this particular Context object.
     mech: &'static str,
+    /// the iteration of the mechanism that's using this Context object.
+    iteration: u64,
+    /// the id of the item that the context is centered on.
+    origin: usize,
     /// the set of item ids in the immediate context.
-    items: HashSet<usize>,
+    items: BTreeSet<usize>,
+    /// the set of item ids given by .get() in place of the immediate
+    /// context, under an Ablation.
+    ablated: Option<BTreeSet<usize>>,
     /// the set of item ids within a small boundary over the immediate
     /// context.
-    frontier: HashSet<usize>,
+    frontier: BTreeSet<usize>,
     /// the epoch that this Context object was created in.
     initial_epoch: usize,
     /// the epoch that this Context currently corresponds to.
     current_epoch: usize,
 }
 
-impl Context {
+impl<T: Payload> Context<T> {
     pub fn add_item_count(&self, id: usize, count: u64) {
-        self.net.item_count(self.current_epoch, id, count)
+        self.net.item_count(self.cause(), self.current_epoch, id, count)
+    }
+    /// report records how well the mechanism is doing as of this
+    /// iteration, as a scalar where higher is better, such as a hit rate.
+    /// Only the latest report in an iteration is kept. Reports can be
+    /// used to stop a run once they plateau.
+    pub fn report(&self, progress: f64) {
+        self.net.report(self.mech, self.iteration, progress)
     }
-    pub fn get(&self) -> Vec<(usize, &'static str, Rc<String>)> {
-        self.net.ids_to_contents(self.items.clone())
+    pub fn get(&self) -> Vec<(usize, &'static str, Rc<T>)> {
+        let items = self.ablated.as_ref().unwrap_or(&self.items);
+        self.net.ids_to_contents(items.clone())
     }
-    pub fn explore(&self) -> Vec<(usize, &'static str, Rc<String>)> {
+    /// ablation gives the Ablation, if any, that replaces what .get()
+    /// gives.
+    pub fn ablation(&self) -> Option<Ablation> {
+        self.net.net.borrow().policy.ablation
+    }
+    pub fn explore(&self) -> Vec<(usize, &'static str, Rc<T>)> {
         self.net
             .ids_to_contents(self.items.union(&self.frontier).cloned())
     }
+    /// `explore_within` is like .explore(), but with a frontier of every
+    /// item at most k hops from the immediate context rather than just 1.
+    pub fn explore_within(&self, k: usize) -> Vec<(usize, &'static str, Rc<T>)> {
+        self.net.ids_to_contents(self.net.within(&self.items, k).into_keys())
+    }
+    /// `explore_ranked` is like .explore_within(), but each item is given
+    /// with its distance from the immediate context (0 for items within
+    /// it). Items are sorted by distance, nearest first, and then by
+    /// popularity since this Context was created, most popular first.
+    pub fn explore_ranked(&self, k: usize) -> Vec<(usize, &'static str, Rc<T>, usize)> {
+        let dist = self.net.within(&self.items, k);
+        self.net.ranked(dist, self.initial_epoch)
+    }
     /// update will give a new Context object that accounts for any changes
     /// that may have happened (such as from .orient() or .grow()) since
     /// this Context object was created.
-    pub fn update(&self) -> Context {
+    pub fn update(&self) -> Context<T> {
         Context {
             initial_epoch: self.initial_epoch,
-            ..self.net.context(self.mech)
+            ..self.net.context(self.mech, self.iteration)
         }
     }
+    /// origin gives the id of the item that this context is centered on:
+    /// the item last oriented to or grown.
+    pub fn origin(&self) -> usize {
+        self.origin
+    }
+    /// size gives the number of items in the whole network, not just in
+    /// this context.
+    pub fn size(&self) -> usize {
+        self.net.size()
+    }
     pub fn orient(&self, id: usize) {
-        self.net.orient(self.initial_epoch, id)
+        self.net.orient(self.cause(), self.initial_epoch, id)
     }
-    pub fn grow_for_mech(&self, mech: &'static str, data: String) -> usize {
-        self.net.grow(mech, data, self.initial_epoch)
+    pub fn grow_for_mech(&self, mech: &'static str, data: T) -> usize {
+        self.net.grow(self.cause(), mech, data, self.initial_epoch, &self.items)
     }
-    pub fn grow(&self, data: String) -> usize {
+    pub fn grow(&self, data: T) -> usize {
         self.grow_for_mech(self.mech, data)
     }
+    /// provenance tells how an item came to be in the network: which
+    /// mechanism grew it, when, and from which antecedents.
+    pub fn provenance(&self, id: usize) -> Option<Provenance> {
+        self.net.provenance(id)
+    }
+    /// lineage gives the provenance of an item followed by that of every
+    /// item it descends from, nearest first.
+    pub fn lineage(&self, id: usize) -> Vec<(usize, Provenance)> {
+        self.net.lineage(id)
+    }
+    /// `grown_from` gives the item that the network was centered on when an
+    /// item was grown, if the item wasn't in the embryo and neither has
+    /// been retired.
+    pub fn grown_from(&self, id: usize) -> Option<usize> {
+        self.net.grown_from(id)
+    }
+    fn cause(&self) -> Cause {
+        Cause {
+            mech: self.mech,
+            iteration: self.iteration,
+        }
+    }
+}
+
+/// `seeded_rng` creates the random number generator for a given seed and
+/// iteration.
+fn seeded_rng(seed: u64, iteration: u64) -> StdRng {
+    StdRng::from_seed(&[seed as usize, (seed >> 32) as usize, iteration as usize])
+}
+
+/// `ablation_rng` creates the random number generator for an ablated
+/// context. It is apart from the network's, so that ablation doesn't
+/// change how the network grows, and depends on the epoch so that every
+/// Context of an epoch is ablated alike.
+fn ablation_rng(seed: u64, iteration: u64, epoch: usize) -> StdRng {
+    StdRng::from_seed(&[seed as usize, (seed >> 32) as usize, iteration as usize, epoch, 1])
+}
+
+/// Cause identifies the mechanism and iteration responsible for a change
+/// to the network.
+#[derive(Clone, Copy, Debug)]
+struct Cause {
+    mech: &'static str,
+    iteration: u64,
 }
 
 #[derive(Debug)]
-struct Net {
+struct Net<T> {
     /// The minimum context size, used only if larger than embryo size (otherwise the embryo size
     /// is used). The context size may grow from this amount as the network grows.
     context_min_size: usize,
     /// For obvious reasons, an upper-bound may be set on the network size.
+    /// Items are retired to make room for new ones once it is reached.
     max_size: usize,
-    /// Graph maps id -> item.
-    graph: Vec<Item>,
+    /// Graph maps id -> item. Retired items are removed, so ids are not
+    /// contiguous.
+    graph: BTreeMap<usize, Item>,
+    /// Data maps id -> the payload of each item in the graph.
+    data: BTreeMap<usize, Rc<T>>,
+    /// The id that the next grown item will have.
+    next_id: usize,
     /// Epochs records the id of each orient call (context-switch), its
     /// associated context, and the ids of accessed items in that epoch.
-    epochs: Vec<(usize, HashSet<usize>, HashSet<usize>)>,
+    epochs: Vec<(usize, BTreeSet<usize>, BTreeSet<usize>)>,
+    /// Seed determines the network's entire history.
+    seed: u64,
+    /// Rng is the source of all randomness in the network. It is reseeded
+    /// from the seed at the start of each iteration, so that a network
+    /// resumed from a checkpoint continues exactly as it would have.
+    rng: StdRng,
+    /// Policy determines how the network is shaped.
+    policy: Policy,
+    /// Observers are notified of every event in the network, including
+    /// any journal.
+    observers: Observers,
+    /// Progress maps a mechanism name to the progress it has reported in
+    /// each iteration. It is not saved with the network.
+    progress: BTreeMap<&'static str, Vec<(u64, f64)>>,
+}
+
+/// Policy is the configurable behavior of a Net, which is kept when the
+/// Net is replaced by a restored one.
+#[derive(Debug)]
+struct Policy {
+    /// Strategy selects the context when orienting.
+    strategy: Box<dyn ContextStrategy>,
+    /// Attachment selects the neighbors of a new item when growing.
+    attachment: Box<dyn AttachmentStrategy>,
+    /// Eviction selects items to retire when the network is full.
+    eviction: Eviction,
+    /// Decay weighs items' accesses by age when measuring popularity.
+    decay: Decay,
+    /// Ablation, if any, replaces the context that mechanisms get.
+    ablation: Option<Ablation>,
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy {
+            strategy: Box::new(strategy::Greedy),
+            attachment: Box::new(strategy::Popularity),
+            eviction: Eviction::default(),
+            decay: Decay::default(),
+            ablation: None,
+        }
+    }
 }
 
-#[derive(Clone)]
-struct Network {
-    net: Rc<RefCell<Net>>,
+impl<T: Payload> Net<T> {
+    /// `close_epoch` updates edge weights at the end of the latest epoch:
+    /// edges between two items that were both accessed are strengthened,
+    /// and all others relax towards the base weight.
+    fn close_epoch(&mut self) {
+        let accessed = &self.epochs[self.epochs.len() - 1].2;
+        for item in self.graph.values_mut() {
+            let active = accessed.contains(&item.id);
+            for oid in &item.adj {
+                if active && accessed.contains(oid) {
+                    let w = *item.weights.get(oid).unwrap_or(&1f64);
+                    item.weights.insert(*oid, w + EDGE_REINFORCE);
+                } else if let Some(w) = item.weights.get_mut(oid) {
+                    *w = 1f64 + (*w - 1f64) * EDGE_DECAY;
+                }
+            }
+        }
+    }
+    /// notify gives an event to every observer. The event is only made if
+    /// there are any.
+    fn notify<F>(&mut self, event: F)
+        where F: FnOnce(&Net<T>) -> Event
+    {
+        if !self.observers.is_empty() {
+            let mut observers = mem::take(&mut self.observers);
+            observers.notify(&event(self));
+            self.observers = observers;
+        }
+    }
+}
+
+struct Network<T> {
+    net: Rc<RefCell<Net<T>>>,
 }
 
-impl Network {
+impl<T> Clone for Network<T> {
+    fn clone(&self) -> Network<T> {
+        Network { net: self.net.clone() }
+    }
+}
+
+impl<T: Payload> Network<T> {
     /// embryo is a collection of starting items to form an initial clique
-    /// graph, of the form (mechanism name, data). Must be non-empty.
-    pub fn new<U>(embryo: U) -> Network
-        where U: IntoIterator<Item = (&'static str, String)>
+    /// graph, of the form (mechanism name, data). Must be non-empty. The
+    /// seed determines all random choices made by the network.
+    pub fn new<U>(embryo: U, seed: u64) -> Network<T>
+        where U: IntoIterator<Item = (&'static str, T)>
     {
         let network = Network {
             net: Rc::new(RefCell::new(Net {
                                           context_min_size: CTX_MIN_SIZE,
                                           max_size: NET_MAX_SIZE,
-                                          graph: Vec::new(),
+                                          graph: BTreeMap::new(),
+                                          data: BTreeMap::new(),
+                                          next_id: 0,
                                           epochs: Vec::new(),
+                                          seed,
+                                          rng: seeded_rng(seed, 0),
+                                          policy: Policy::default(),
+                                          observers: Observers::default(),
+                                          progress: BTreeMap::new(),
                                       })),
         };
         {
@@ -158,20 +425,18 @@ impl Network {
             let mut net = network.net.borrow_mut();
             // clique of embryo as base
             let mut id = 0;
-            let embryo: Vec<(&'static str, String)> = embryo.into_iter().collect();
-            let edges: HashSet<usize> = (0..embryo.len()).collect();
-            net.graph = embryo
-                .into_iter()
-                .map(|(mech, data)| {
-                         let mut edges = edges.clone();
-                         edges.remove(&id);
-                         let item = Item::new(mech, edges, data, id);
-                         id += 1;
-                         item
-                     })
-                .collect();
+            let embryo: Vec<(&'static str, T)> = embryo.into_iter().collect();
+            let edges: BTreeSet<usize> = (0..embryo.len()).collect();
+            for (mech, data) in embryo {
+                let mut edges = edges.clone();
+                edges.remove(&id);
+                net.graph.insert(id, Item::new(mech, edges, id));
+                net.data.insert(id, Rc::new(data));
+                id += 1;
+            }
+            net.next_id = id;
             // initial epoch has no accesses and context of entire embyro
-            net.epochs.push((0, edges, HashSet::new())); // edges ~ embryo ids
+            net.epochs.push((0, edges, BTreeSet::new())); // edges ~ embryo ids
             // initial size
             let size = id;
             assert_ne!(0, size);
@@ -181,278 +446,847 @@ impl Network {
         }
         network
     }
+    /// mechs gives the names of every mechanism that made an item in the
+    /// network.
+    fn mechs(&self) -> Vec<&'static str> {
+        let net = self.net.borrow();
+        let mut mechs: Vec<&'static str> = net.graph.values().map(|item| item.mech).collect();
+        mechs.sort();
+        mechs.dedup();
+        mechs
+    }
+    /// report records a mechanism's progress for an iteration, replacing
+    /// any earlier report for the same iteration.
+    fn report(&self, mech: &'static str, iteration: u64, progress: f64) {
+        let mut net = self.net.borrow_mut();
+        let reports = net.progress.entry(mech).or_default();
+        if reports.last().is_some_and(|&(t, _)| t == iteration) {
+            reports.pop();
+        }
+        reports.push((iteration, progress));
+    }
+    /// notify gives an event to the network's observers.
+    fn notify(&self, event: Event) {
+        self.net.borrow_mut().notify(|_| event)
+    }
+    /// `next_id` gives the id that the next grown item will have.
+    fn next_id(&self) -> usize {
+        self.net.borrow().next_id
+    }
+    /// `grown_by_since` tells whether the named mechanism has grown any
+    /// item, still in the network, with an id of at least the given one.
+    fn grown_by_since(&self, mech: &str, id: usize) -> bool {
+        let net = self.net.borrow();
+        net.graph.range(id..).any(|(_, item)| item.provenance.mech == mech)
+    }
+    /// reseed resets the source of randomness for the given iteration.
+    fn reseed(&self, iteration: u64) {
+        let mut net = self.net.borrow_mut();
+        net.rng = seeded_rng(net.seed, iteration);
+    }
     /// item_count increases the count of a given item corresponding to the
-    /// given epoch.
-    fn item_count(&self, epoch: usize, id: usize, count: u64) {
+    /// given epoch. Counts for retired items are ignored.
+    fn item_count(&self, cause: Cause, epoch: usize, id: usize, count: u64) {
         let mut net = self.net.borrow_mut();
-        {
-            let item = &mut net.graph[id];
-            item.add_count(epoch, count);
+        match net.graph.get_mut(&id) {
+            Some(item) => item.add_count(epoch, count),
+            None => return,
         }
         net.epochs[epoch].2.insert(id);
+        net.notify(|_| Event::count(cause, epoch, id, count));
     }
     /// ids_to_contexts takes an iterable of item ids and returns a vector
-    /// of (id, mechanism name, data) corresponding to each given id.
-    fn ids_to_contents<U>(&self, items: U) -> Vec<(usize, &'static str, Rc<String>)>
+    /// of (id, mechanism name, data) corresponding to each given id that
+    /// hasn't been retired.
+    fn ids_to_contents<U>(&self, items: U) -> Vec<(usize, &'static str, Rc<T>)>
         where U: IntoIterator<Item = usize>
     {
         let net = self.net.borrow();
         items
             .into_iter()
-            .map(move |id| {
-                     let item = &net.graph[id];
-                     (id, item.mech, item.data.clone())
-                 })
+            .filter_map(move |id| {
+                            net.graph
+                                .get(&id)
+                                .map(|item| (id, item.mech, net.data[&id].clone()))
+                        })
             .collect()
     }
     /// orient creates a new epoch, centering the context around the given
     /// item and using items' access counts since the given epoch to
-    /// determine where to grow the context.
-    fn orient(&self, epoch: usize, id: usize) {
+    /// determine where to grow the context. The shape of the context is
+    /// decided by the network's ContextStrategy. Orienting to a retired
+    /// item does nothing.
+    fn orient(&self, cause: Cause, epoch: usize, id: usize) {
         let mut net = self.net.borrow_mut();
-        let mut ctx: HashSet<usize>;
+        if !net.graph.contains_key(&id) {
+            return;
+        }
+        net.close_epoch();
         let n = net.graph.len();
-        if n < net.context_min_size {
+        let ctx: BTreeSet<usize> = if n < net.context_min_size {
             // use the entire network
-            ctx = (0..n).collect();
+            net.graph.keys().cloned().collect()
         } else {
-            // the context should be sized according to the expected max
-            // degree of a scale-free network with size n:
-            //  k_max ~ n^{1/(gamma-1)}
-            // for degree exponent 2 < gamma < 3.
-            let mut rng = rand::thread_rng();
-            let exp = Range::new(0.5f64, 1f64).ind_sample(&mut rng);
-            let prop = 0.5;
-            let kmax = prop * (n as f64).powf(exp);
-            let size = max(net.context_min_size, min(n, kmax as usize));
-
-            // start with the given node. add its neighbors in order by
-            // popularity, select most popular neighbor and repeat. stop
-            // when we get to `size`.
-            ctx = HashSet::new();
-            ctx.insert(id);
-            let mut selected = id;
-            while ctx.len() < size {
-                let mut ext: Vec<usize> = net.graph[selected]
-                    .adj
-                    .difference(&ctx)
-                    .cloned()
-                    .collect();
-                if ext.is_empty() {
-                    break;
-                }
-                if ext.len() + ctx.len() > size {
-                    // truncate less-used items
-                    let take = size - ctx.len();
-                    ext.sort_by_key(|&id| {
-                                        let item = &net.graph[id];
-                                        -(item.adj.len() as i64)
-                                    });
-                    ctx.extend(ext.iter().take(take));
-                    break;
-                }
-                selected = *ext.iter()
-                    .max_by_key(|&id| {
-                                    let item = &net.graph[*id];
-                                    item.recent_count(epoch)
-                                })
-                    .unwrap();
-                ctx.extend(ext);
-            }
-        }
-        net.epochs.push((id, ctx, HashSet::new()))
+            let net = &mut *net;
+            let view = NetView {
+                graph: &net.graph,
+                epochs: &net.epochs,
+                context: &net.epochs[net.epochs.len() - 1].1,
+                context_min_size: net.context_min_size,
+                decay: net.policy.decay,
+            };
+            net.policy.strategy.select(&view, id, epoch, &mut net.rng)
+        };
+        net.notify(|_| Event::orient(cause, epoch, id, &ctx));
+        net.epochs.push((id, ctx, BTreeSet::new()));
+        net.notify(|net| {
+                       Event::Epoch {
+                           epoch: net.epochs.len() - 1,
+                           origin: id,
+                       }
+                   });
     }
-    /// grow adds a new knowledge artifact (Item) to the network, and
-    /// creates a new epoch with an implicit call to .orient() on the new
-    /// item.
-    fn grow(&self, mech: &'static str, data: String, epoch: usize) -> usize {
+    /// close closes the latest epoch, if anything was accessed in it, so
+    /// its co-accesses weight the edges, and opens a new one with the same
+    /// origin and context. It is journaled as an orient by "run".
+    fn close(&self, iteration: u64) {
+        let mut net = self.net.borrow_mut();
+        let (id, ctx) = match net.epochs.last() {
+            Some(&(id, ref ctx, ref accessed)) if !accessed.is_empty() => (id, ctx.clone()),
+            _ => return,
+        };
+        net.close_epoch();
+        let cause = Cause {
+            mech: "run",
+            iteration,
+        };
+        let epoch = net.epochs.len() - 1;
+        net.notify(|_| Event::orient(cause, epoch, id, &ctx));
+        net.epochs.push((id, ctx, BTreeSet::new()));
+        net.notify(|net| {
+                       Event::Epoch {
+                           epoch: net.epochs.len() - 1,
+                           origin: id,
+                       }
+                   });
+    }
+    /// grow adds a new knowledge artifact (Item) to the network, wired in
+    /// by the network's AttachmentStrategy from the growing Context's
+    /// items, and creates a new epoch with an implicit call to .orient() on
+    /// the new item.
+    fn grow(&self, cause: Cause, mech: &'static str, data: T, epoch: usize, items: &BTreeSet<usize>) -> usize {
         let id: usize;
         {
             let mut net = self.net.borrow_mut();
-            id = net.graph.len();
-            assert!(id <= net.max_size);
+            id = net.next_id;
+            net.next_id += 1;
 
-            // compute counts for antecedent artifacts
-            let ids: HashSet<usize> = net.epochs
-                .iter()
-                .skip(epoch) // look for accesses as early as this epoch
-                .flat_map(|&(_, ref cx, ref ru)| cx.union(ru).cloned())
-                .collect(); // removes duplicates
-            let mut sum = 0;
-            let counts: HashSet<(usize, u64)> = ids.iter()
-                .map(|&id| {
-                         let item = &net.graph[id];
-                         let count = 1 + item.recent_count(epoch);
-                         sum += count;
-                         (id, count)
-                     })
-                .collect();
-
-            // convert counts to probabilities
-            let antecedents: HashMap<usize, f64> = counts
-                .iter()
-                .map(|&(id, cnt)| {
-                         let p = (cnt as f64) / (sum as f64);
-                         (id, p)
-                     })
-                .collect();
-            let mut edges = HashSet::new();
-
-            // popularity-based subset selection
-            let uniform = Range::new(0f64, 1.);
-            let mut rng = rand::thread_rng();
-            for (id, p) in &antecedents {
-                let sample = uniform.ind_sample(&mut rng);
-                let connect = sample <= *p;
-                if connect {
-                    edges.insert(*id);
-                }
-            }
-            // CRP if we got an empty subset
-            if edges.is_empty() {
-                let mut r = uniform.ind_sample(&mut rng);
-                for (id, p) in &antecedents {
-                    r -= *p;
-                    if r < 0f64 {
-                        edges.insert(*id);
-                        break;
-                    }
-                }
+            // make room for the new item
+            while !net.graph.is_empty() && net.graph.len() >= net.max_size {
+                let victim = net.policy.eviction.victim(&net).unwrap();
+                evict::retire(&mut net, victim);
+                net.notify(|_| Event::evict(cause, epoch, victim));
             }
 
+            // the growing Context may hold items that were just retired.
+            let items: BTreeSet<usize> = items.iter().filter(|id| net.graph.contains_key(id)).cloned().collect();
+            let Attachment { edges, antecedents } = {
+                let net = &mut *net;
+                let view = NetView {
+                    graph: &net.graph,
+                    epochs: &net.epochs,
+                    context: &items,
+                    context_min_size: net.context_min_size,
+                    decay: net.policy.decay,
+                };
+                net.policy.attachment.attach(&view, epoch, &mut net.rng)
+            };
+
             // update other end of new edges (undirected network)
             for oid in &edges {
-                let item = &mut net.graph[*oid];
-                item.adj.insert(id);
+                let item = net.graph.get_mut(oid).unwrap();
+                item.link(id);
             }
 
             // actually add the item
-            let item = Item::new(mech, edges, data, id);
-            net.graph.push(item);
+            let mut item = Item::new(mech, edges, id);
+            item.provenance = Provenance {
+                mech: cause.mech,
+                iteration: cause.iteration,
+                epoch: net.epochs.len(), // the epoch created by orienting to it
+                parents: item.adj.clone(),
+                antecedents,
+            };
+            net.graph.insert(id, item);
+            net.data.insert(id, Rc::new(data));
+            net.notify(|net| Event::grow(cause, epoch, &net.graph[&id], &*net.data[&id]));
         }
-        self.orient(epoch, id);
+        self.orient(cause, epoch, id);
         id
     }
+    /// provenance gives the provenance of an item, if it hasn't been
+    /// retired.
+    fn provenance(&self, id: usize) -> Option<Provenance> {
+        let net = self.net.borrow();
+        net.graph.get(&id).map(|item| item.provenance.clone())
+    }
+    /// lineage gives the provenance of an item and of every item it
+    /// descends from through its parents, nearest first. Retired ancestors
+    /// and their own ancestors are omitted.
+    fn lineage(&self, id: usize) -> Vec<(usize, Provenance)> {
+        let net = self.net.borrow();
+        let mut lineage = Vec::new();
+        let mut seen = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        queue.push_back(id);
+        seen.insert(id);
+        while let Some(id) = queue.pop_front() {
+            if let Some(item) = net.graph.get(&id) {
+                for &parent in &item.provenance.parents {
+                    if seen.insert(parent) {
+                        queue.push_back(parent);
+                    }
+                }
+                lineage.push((id, item.provenance.clone()));
+            }
+        }
+        lineage
+    }
+    /// `grown_from` gives the origin of the epoch in which an item was
+    /// grown, before the item was oriented to.
+    fn grown_from(&self, id: usize) -> Option<usize> {
+        let net = self.net.borrow();
+        let epoch = net.graph.get(&id)?.provenance.epoch;
+        if epoch == 0 {
+            return None;
+        }
+        let origin = net.epochs.get(epoch - 1)?.0;
+        if net.graph.contains_key(&origin) { Some(origin) } else { None }
+    }
+    /// size gives the number of items in the network.
+    pub fn size(&self) -> usize {
+        self.net.borrow().graph.len()
+    }
+    /// stats measures the shape of the network.
+    pub fn stats(&self) -> Stats {
+        let net = self.net.borrow();
+        Stats::of(net.graph.iter().map(|(&id, item)| (id, &item.adj)))
+    }
     /// frontier_of takes a set of item ids and returns the set of item ids
     /// corresponding to all adjacent items.
-    fn frontier_of(&self, items: &HashSet<usize>) -> HashSet<usize> {
+    fn frontier_of(&self, items: &BTreeSet<usize>) -> BTreeSet<usize> {
         let net = self.net.borrow();
-        let frontier: HashSet<usize> = items
+        let frontier: BTreeSet<usize> = items
             .iter()
-            .flat_map(|&id| {
-                          let item = &net.graph[id];
-                          item.adj.clone()
-                      })
+            .filter_map(|id| net.graph.get(id))
+            .flat_map(|item| item.adj.clone())
             .collect();
         frontier.difference(items).cloned().collect()
     }
+    /// within takes a set of item ids and returns the ids of all items at
+    /// most k hops away from any of them, each with its distance.
+    fn within(&self, items: &BTreeSet<usize>, k: usize) -> BTreeMap<usize, usize> {
+        let net = self.net.borrow();
+        let mut dist: BTreeMap<usize, usize> = items
+            .iter()
+            .filter(|id| net.graph.contains_key(id))
+            .map(|&id| (id, 0))
+            .collect();
+        let mut queue: VecDeque<usize> = dist.keys().cloned().collect();
+        while let Some(id) = queue.pop_front() {
+            let d = dist[&id];
+            if d == k {
+                continue;
+            }
+            for &oid in &net.graph[&id].adj {
+                if let Entry::Vacant(e) = dist.entry(oid) {
+                    e.insert(d + 1);
+                    queue.push_back(oid);
+                }
+            }
+        }
+        dist
+    }
+    /// ranked gives the contents of items with their distances, nearest
+    /// first and then most popular since the given epoch first.
+    fn ranked(&self,
+              dist: BTreeMap<usize, usize>,
+              epoch: usize)
+              -> Vec<(usize, &'static str, Rc<T>, usize)> {
+        let net = self.net.borrow();
+        let now = net.epochs.len() - 1;
+        let mut ranked: Vec<(usize, f64, &Item)> = dist
+            .into_iter()
+            .filter_map(|(id, d)| net.graph.get(&id).map(|item| (d, item)))
+            .map(|(d, item)| (d, net.policy.decay.popularity(item, epoch, now), item))
+            .collect();
+        ranked.sort_by(|a, b| {
+            a.0.cmp(&b.0).then(b.1.partial_cmp(&a.1).unwrap()).then(a.2.id.cmp(&b.2.id))
+        });
+        ranked
+            .into_iter()
+            .map(|(d, _, item)| (item.id, item.mech, net.data[&item.id].clone(), d))
+            .collect()
+    }
     /// context creates a new Context object corresponding to the network's
     /// latest epoch.
-    fn context(&self, mech: &'static str) -> Context {
+    fn context(&self, mech: &'static str, iteration: u64) -> Context<T> {
         let net = self.net.borrow();
         let epoch = net.epochs.len() - 1;
-        let items = net.epochs[epoch].1.clone();
+        let (origin, ref items, _) = net.epochs[epoch];
+        let items = items.clone();
         let frontier = self.frontier_of(&items);
+        let ablated = net.policy.ablation.map(|ablation| {
+            let mut rng = ablation_rng(net.seed, iteration, epoch);
+            ablation.select(&net.graph, items.len(), &mut rng)
+        });
         Context {
             net: self.clone(),
-            mech: mech,
-            items: items,
-            frontier: frontier,
+            mech,
+            iteration,
+            origin,
+            items,
+            ablated,
+            frontier,
             initial_epoch: epoch,
             current_epoch: epoch,
         }
     }
-    /// dot writes the network in the graphviz DOT language.
+    /// dot writes the network in the graphviz DOT language, with edges
+    /// labeled by their weight.
     fn dot<W>(&self, w: &mut W) -> ::std::io::Result<()>
         where W: Write
     {
         let net = self.net.borrow();
         let mut body = String::new();
-        for id in 0..net.graph.len() {
-            let label = format!("id={}  {}", id, (*net.graph[id].data).clone());
+        for (id, data) in &net.data {
+            let data = match serde_json::to_value(&**data) {
+                Ok(serde_json::Value::String(s)) => s,
+                Ok(v) => v.to_string(),
+                Err(err) => format!("<{}>", err),
+            };
+            let label = format!("id={}  {}", id, data);
             body.push_str(format!("  N{} [shape=box,label={:?}];\n", id, label).as_str());
         }
         body.pop();
         let mut edges = net.graph
-            .iter()
+            .values()
             .flat_map(|item| {
                 let id = item.id;
                 item.adj
                     .iter()
                     .map(move |&o| {
-                             let mut v = vec![id, o];
-                             v.sort();
-                             (v[0], v[1])
+                             (min(id, o), max(id, o))
                          })
             })
             .collect::<Vec<_>>();
         edges.sort();
         edges.dedup();
         for (i, o) in edges {
-            body.push_str(format!("\n  N{} -- N{};", i, o).as_str());
+            let weight = net.graph[&i].weight(o);
+            body.push_str(format!("\n  N{} -- N{} [label=\"{:.2}\",penwidth={:.2}];",
+                                  i,
+                                  o,
+                                  weight,
+                                  weight.sqrt())
+                                  .as_str());
         }
         write!(w, "graph G {{\n{}\n}}\n", body)
     }
 }
 
+/// Mechanism is something that uses and shapes the knowledge network.
+/// Only .step() is required, and any function which takes a Context and an
+/// iteration number, and returns an Outcome, is a Mechanism that does
+/// nothing else.
+pub trait Mechanism<T = String> {
+    /// init is called once when .run() begins, before the first step.
+    fn init(&mut self) {}
+    /// step is called on every iteration with a Context refreshed
+    /// according to the latest epoch of the knowledge network. A failed
+    /// step is handled by the Skn's OnFailure.
+    fn step(&mut self, ctx: Context<T>, iteration: u64) -> Result<(), Box<dyn Error>>;
+    /// `after_iteration` is called at the end of every iteration, once
+    /// every mechanism has stepped, with a Context of the network as they
+    /// left it.
+    fn after_iteration(&mut self, _ctx: Context<T>, _iteration: u64) {}
+    /// finish is called once when .run() ends, after the last iteration.
+    fn finish(&mut self) {}
+    /// save gives the state that the mechanism keeps across iterations,
+    /// if any, to be written with each Checkpoint. Mechanisms that keep no
+    /// state needn't implement it.
+    fn save(&self) -> Option<serde_json::Value> {
+        None
+    }
+    /// restore takes back the state given by .save() when a run resumes
+    /// from a Checkpoint. It is called after .init() and before the first
+    /// step.
+    fn restore(&mut self, _state: serde_json::Value) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Outcome is what a function used as a Mechanism gives from a step:
+/// nothing, for a step that can't fail, or a Result.
+pub trait Outcome {
+    fn into_result(self) -> Result<(), Box<dyn Error>>;
+}
+
+impl Outcome for () {
+    fn into_result(self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+impl<E> Outcome for Result<(), E>
+    where E: Into<Box<dyn Error>>
+{
+    fn into_result(self) -> Result<(), Box<dyn Error>> {
+        self.map_err(Into::into)
+    }
+}
+
+impl<T, F, O> Mechanism<T> for F
+    where F: FnMut(Context<T>, u64) -> O,
+          O: Outcome
+{
+    fn step(&mut self, ctx: Context<T>, iteration: u64) -> Result<(), Box<dyn Error>> {
+        self(ctx, iteration).into_result()
+    }
+}
+
+/// Registered is a mechanism as kept by a `MechanismRegistry`.
+struct Registered<'a, T> {
+    name: &'static str,
+    mech: RefCell<Box<dyn Mechanism<T> + 'a>>,
+    schedule: Schedule,
+    /// the id of the next item to be grown as of when the mechanism last
+    /// stepped, for telling which items were grown since.
+    mark: Cell<usize>,
+}
+
 /// `MechanismRegistry` maintains a set of mechanisms used by the knowledge
-/// network. A mechanism is a function which takes a Context and an
-/// iteration number.
-struct MechanismRegistry<'a> {
-    reg: Vec<(&'static str, &'a Fn(Context, u64))>,
+/// network, in the order that they step.
+struct MechanismRegistry<'a, T> {
+    reg: Vec<Registered<'a, T>>,
 }
 
-impl<'a> MechanismRegistry<'a> {
-    fn new() -> MechanismRegistry<'a> {
+impl<'a, T> MechanismRegistry<'a, T> {
+    fn new() -> MechanismRegistry<'a, T> {
         MechanismRegistry { reg: Vec::new() }
     }
-    fn register(&mut self, name: &'static str, mech: &'a Fn(Context, u64)) {
-        self.reg.push((name, mech));
+    fn register<M>(&mut self, name: &'static str, mech: M, schedule: Schedule)
+        where M: Mechanism<T> + 'a
+    {
+        self.reg.push(Registered {
+                          name,
+                          mech: RefCell::new(Box::new(mech)),
+                          schedule,
+                          mark: Cell::new(0),
+                      });
+        // stable, so equal priorities keep the order of registration
+        self.reg.sort_by_key(|r| -r.schedule.priority);
     }
 }
 
 /// Skn maintains a knowledge network and the mechanisms interacting with it.
-pub struct Skn<'a> {
-    network: Network,
-    reg: MechanismRegistry<'a>,
+/// Every item in the network holds data of the same Payload type.
+pub struct Skn<'a, T = String> {
+    network: Network<T>,
+    reg: MechanismRegistry<'a, T>,
     t: u64,
+    /// the first iteration that .run() will perform.
+    start: u64,
+    /// where a Checkpoint is written after each iteration, if anywhere.
+    checkpoint: Option<PathBuf>,
+    /// where Stats are written after each iteration, if anywhere.
+    stats: Option<RefCell<Box<dyn Write>>>,
+    /// criteria for ending a run early.
+    stops: Vec<Stop>,
+    /// what to do when a mechanism fails a step.
+    on_failure: OnFailure,
+    /// failures records every failed step as (iteration, mechanism,
+    /// reason).
+    failures: RefCell<Vec<(u64, &'static str, String)>>,
+    /// the state of each mechanism from a Checkpoint being resumed, to be
+    /// restored when .run() begins.
+    resumed: BTreeMap<String, serde_json::Value>,
 }
-impl<'a> fmt::Debug for Skn<'a> {
+impl<'a, T: Payload> fmt::Debug for Skn<'a, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let net = self.network.net.borrow();
         write!(f, "Skn {{ net: {:?} }}", net)
     }
 }
 
-impl<'a> Skn<'a> {
+impl<'a, T: Payload> Skn<'a, T> {
     /// embryo is a non-empty collection of initial knowledge artifacts of
     /// the form (mechanism name, data), and iterations is the number of
-    /// iterations to run each mechanism.
-    pub fn new<U>(embryo: U, iterations: u64) -> Skn<'a>
-        where U: IntoIterator<Item = (&'static str, String)>
+    /// iterations to run each mechanism. The network is seeded randomly;
+    /// use `Skn::with_seed` for a reproducible network.
+    pub fn new<U>(embryo: U, iterations: u64) -> Skn<'a, T>
+        where U: IntoIterator<Item = (&'static str, T)>
+    {
+        Skn::with_seed(embryo, iterations, rand::thread_rng().gen())
+    }
+    /// `with_seed` is like `Skn::new`, but the given seed determines every
+    /// random choice made by the network, so that the same seed and the
+    /// same mechanism behavior reproduce the same network exactly.
+    pub fn with_seed<U>(embryo: U, iterations: u64, seed: u64) -> Skn<'a, T>
+        where U: IntoIterator<Item = (&'static str, T)>
     {
         Skn {
-            network: Network::new(embryo),
+            network: Network::new(embryo, seed),
             reg: MechanismRegistry::new(),
             t: iterations,
+            start: 1,
+            checkpoint: None,
+            stats: None,
+            stops: Vec::new(),
+            on_failure: OnFailure::default(),
+            failures: RefCell::new(Vec::new()),
+            resumed: BTreeMap::new(),
         }
     }
-    /// register adds a new mechanism, given by its name and a function
-    /// which takes a Context and an iteration number, for use with the
-    /// knowledge network.
-    pub fn register(&mut self, name: &'static str, mech: &'a Fn(Context, u64)) {
-        self.reg.register(name, mech);
-    }
-    /// run calls each mechanism `iteration` number of times (set when this
-    /// Skn was created) with a refreshed context on each iteration
-    /// (according to the latest epoch of the knowledge network).
-    pub fn run(&self) {
-        for t in 1..self.t + 1 {
-            for &(name, mech) in &self.reg.reg {
-                let ctx = self.network.context(name);
-                mech(ctx, t)
+    /// register adds a new mechanism, given by its name and a Mechanism,
+    /// such as a function which takes a Context and an iteration number,
+    /// for use with the knowledge network.
+    pub fn register<M>(&mut self, name: &'static str, mech: M)
+        where M: Mechanism<T> + 'a
+    {
+        self.reg.register(name, mech, Schedule::default());
+    }
+    /// `register_with_schedule` is like .register(), but the mechanism
+    /// steps according to the given Schedule rather than in every
+    /// iteration.
+    pub fn register_with_schedule<M>(&mut self, name: &'static str, mech: M, schedule: Schedule)
+        where M: Mechanism<T> + 'a
+    {
+        self.reg.register(name, mech, schedule);
+    }
+    /// run steps each mechanism `iteration` number of times (set when this
+    /// Skn was created), or as its Schedule allows, with a refreshed context
+    /// on each iteration (according to the latest epoch of the knowledge
+    /// network). If this Skn was resumed, iterations before the checkpoint
+    /// are skipped.
+    /// Mechanisms are initialized before the first iteration, told when
+    /// each iteration is over, and finished after the last, after which the
+    /// latest epoch is closed so its co-accesses weight the edges. Failed steps
+    /// are handled according to the OnFailure (see .on_failure()). If a Stop
+    /// criterion ends the run early, it is returned along with the last
+    /// iteration that was run.
+    pub fn run(&self) -> Option<(u64, Stop)> {
+        let began = Instant::now();
+        let mut idle = 0;
+        let mut stopped = None;
+        for r in &self.reg.reg {
+            r.mark.set(self.network.next_id());
+            let mut mech = r.mech.borrow_mut();
+            mech.init();
+            if let Some(state) = self.resumed.get(r.name) {
+                if let Err(err) = mech.restore(state.clone()) {
+                    eprintln!("could not restore the state of {}; starting it afresh: {}", r.name, err)
+                }
+            }
+        }
+        for t in self.start..self.t + 1 {
+            let next_id = self.network.next_id();
+            self.network.reseed(t);
+            self.network.notify(Event::IterationStart { iteration: t });
+            for r in &self.reg.reg {
+                if !r.schedule.due(t) {
+                    continue;
+                }
+                if let Some(other) = r.schedule.after_growth_by {
+                    if !self.network.grown_by_since(other, r.mark.get()) {
+                        continue;
+                    }
+                }
+                r.mark.set(self.network.next_id());
+                if !self.step(r, t) && self.on_failure == OnFailure::Skip {
+                    break;
+                }
+            }
+            for r in &self.reg.reg {
+                let ctx = self.network.context(r.name, t);
+                r.mech.borrow_mut().after_iteration(ctx, t);
+            }
+            self.network.notify(Event::IterationEnd { iteration: t });
+            if let Some(ref path) = self.checkpoint {
+                if let Err(err) = self.write_checkpoint(path, t + 1) {
+                    eprintln!("could not write checkpoint after iteration {}: {}", t, err)
+                }
+            }
+            if let Some(ref w) = self.stats {
+                let stats = self.stats();
+                let line = stats::Line {
+                    iteration: t,
+                    stats: &stats,
+                };
+                let mut w = w.borrow_mut();
+                if let Err(err) = serde_json::to_writer(&mut *w, &line)
+                       .map_err(::std::io::Error::from)
+                       .and_then(|_| writeln!(w)) {
+                    eprintln!("could not write stats after iteration {}: {}", t, err)
+                }
             }
+            idle = if self.network.next_id() == next_id { idle + 1 } else { 0 };
+            if let Some(stop) = self.stops.iter().find(|stop| self.should_stop(stop, idle, began)) {
+                stopped = Some((t, stop.clone()));
+                break;
+            }
+        }
+        for r in &self.reg.reg {
+            r.mech.borrow_mut().finish();
         }
+        self.network.close(stopped.as_ref().map_or(self.t, |s| s.0));
+        stopped
+    }
+    /// step steps a mechanism, retrying it if the OnFailure says to, and
+    /// tells whether it ultimately succeeded.
+    fn step(&self, r: &Registered<'a, T>, t: u64) -> bool {
+        let attempts = match self.on_failure {
+            OnFailure::Retry(n) => n + 1,
+            _ => 1,
+        };
+        for attempt in 1..attempts + 1 {
+            let ctx = self.network.context(r.name, t);
+            let err = match r.mech.borrow_mut().step(ctx, t) {
+                Ok(()) => return true,
+                Err(err) => err,
+            };
+            let reason = err.to_string();
+            eprintln!("{} failed in iteration {} (attempt {} of {}): {}",
+                      r.name,
+                      t,
+                      attempt,
+                      attempts,
+                      reason);
+            self.network.notify(Event::Failure {
+                                    mech: String::from(r.name),
+                                    iteration: t,
+                                    reason: reason.clone(),
+                                });
+            self.failures.borrow_mut().push((t, r.name, reason));
+        }
+        false
+    }
+    /// `on_failure` sets what .run() does when a mechanism fails a step.
+    /// The default is `OnFailure::Log`.
+    pub fn on_failure(&mut self, on_failure: OnFailure) {
+        self.on_failure = on_failure;
+    }
+    /// failures gives every failed step so far, as (iteration, mechanism,
+    /// reason) in the order they failed.
+    pub fn failures(&self) -> Vec<(u64, &'static str, String)> {
+        self.failures.borrow().clone()
+    }
+    /// `stop_when` makes .run() end early, after the first iteration at
+    /// which the criterion is met. Given several criteria, a run ends when
+    /// any one is met.
+    pub fn stop_when(&mut self, stop: Stop) {
+        self.stops.push(stop);
+    }
+    /// `should_stop` tells whether a criterion is met, given the number of
+    /// iterations in a row that have grown nothing and when the run began.
+    fn should_stop(&self, stop: &Stop, idle: u64, began: Instant) -> bool {
+        match *stop {
+            Stop::NoGrowth(k) => idle >= k,
+            Stop::Plateau { mech, window, tolerance } => {
+                Stop::plateaued(&self.progress(mech), window, tolerance)
+            }
+            Stop::WallClock(budget) => began.elapsed() >= budget,
+            Stop::Size(n) => self.network.size() >= n,
+        }
+    }
+    /// progress gives the progress reported by the named mechanism, as
+    /// (iteration, progress) in the order reported.
+    pub fn progress(&self, mech: &str) -> Vec<(u64, f64)> {
+        let net = self.network.net.borrow();
+        net.progress.get(mech).cloned().unwrap_or_default()
+    }
+    /// `checkpoint_to` makes .run() write a Checkpoint to the given path
+    /// after every iteration, for use with .resume_from().
+    pub fn checkpoint_to<P>(&mut self, path: P)
+        where P: AsRef<Path>
+    {
+        self.checkpoint = Some(path.as_ref().to_path_buf());
+    }
+    /// `resume_from` restores the knowledge network from a Checkpoint
+    /// file, so that .run() continues at the iteration after the one that
+    /// wrote the checkpoint. Mechanisms get back the state they saved in
+    /// it when .run() begins.
+    pub fn resume_from<P>(&mut self, path: P) -> Result<(), LoadError>
+        where P: AsRef<Path>
+    {
+        let f = File::open(path)?;
+        let checkpoint: Checkpoint = serde_json::from_reader(f)?;
+        self.restore(checkpoint.network)?;
+        self.start = checkpoint.iteration;
+        self.resumed = checkpoint.mechanisms;
+        Ok(())
+    }
+    /// `stats_to` makes .run() write the Stats of the network as a line of
+    /// JSON, tagged with the iteration, after every iteration.
+    pub fn stats_to<W>(&mut self, w: W)
+        where W: Write + 'static
+    {
+        self.stats = Some(RefCell::new(Box::new(w)));
+    }
+    /// stats measures the shape of the knowledge network: its degree
+    /// distribution and power-law fit, clustering, and path lengths.
+    pub fn stats(&self) -> Stats {
+        self.network.stats()
+    }
+    /// `write_checkpoint` atomically replaces the file at path with a
+    /// Checkpoint that continues at the given iteration.
+    fn write_checkpoint(&self, path: &Path, iteration: u64) -> ::std::io::Result<()> {
+        let checkpoint = Checkpoint {
+            iteration,
+            network: self.snapshot(),
+            mechanisms: self.reg
+                .reg
+                .iter()
+                .filter_map(|r| r.mech.borrow().save().map(|state| (String::from(r.name), state)))
+                .collect(),
+        };
+        let tmp = path.with_extension("tmp");
+        {
+            let mut f = File::create(&tmp)?;
+            serde_json::to_writer(&mut f, &checkpoint)?;
+            f.sync_all()?;
+        }
+        fs::rename(tmp, path)
+    }
+    /// `set_context_strategy` changes how contexts are selected when a
+    /// mechanism orients. The default is `strategy::Greedy`.
+    pub fn set_context_strategy<S>(&mut self, strategy: S)
+        where S: ContextStrategy + 'static
+    {
+        self.network.net.borrow_mut().policy.strategy = Box::new(strategy);
+    }
+    /// `set_attachment_strategy` changes how new items are connected to
+    /// the network when a mechanism grows it. The default is
+    /// `strategy::Popularity`.
+    pub fn set_attachment_strategy<S>(&mut self, strategy: S)
+        where S: AttachmentStrategy + 'static
+    {
+        self.network.net.borrow_mut().policy.attachment = Box::new(strategy);
+    }
+    /// `set_decay` changes how items' accesses are weighed by age when
+    /// measuring their popularity, both when orienting and when growing.
+    /// The default is `Decay::Window`.
+    ///
+    /// Panics if given a half-life that isn't positive and finite, with
+    /// which popularity would be meaningless.
+    pub fn set_decay(&mut self, decay: Decay) {
+        if let Decay::HalfLife(half_life) = decay {
+            assert!(half_life.is_finite() && half_life > 0f64,
+                    "half-life must be positive and finite, not {}",
+                    half_life);
+        }
+        self.network.net.borrow_mut().policy.decay = decay;
+    }
+    /// `set_ablation` makes mechanisms get a replacement for the context
+    /// chosen for them, such as the whole network, for comparison against
+    /// choosing a context. The default is no ablation.
+    pub fn set_ablation(&mut self, ablation: Ablation) {
+        self.network.net.borrow_mut().policy.ablation = Some(ablation);
+    }
+    /// `set_max_size` changes the number of items the network may hold
+    /// before items are retired to make room for new ones.
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.network.net.borrow_mut().max_size = max_size;
+    }
+    /// `set_eviction` changes how items are chosen for retirement when the
+    /// network is full. The default is `Eviction::LeastRecentlyAccessed`.
+    pub fn set_eviction(&mut self, eviction: Eviction) {
+        self.network.net.borrow_mut().policy.eviction = eviction;
+    }
+    /// provenance tells how an item came to be in the network: which
+    /// mechanism grew it, when, and from which antecedents.
+    pub fn provenance(&self, id: usize) -> Option<Provenance> {
+        self.network.provenance(id)
+    }
+    /// lineage gives the provenance of an item followed by that of every
+    /// item it descends from, nearest first.
+    pub fn lineage(&self, id: usize) -> Vec<(usize, Provenance)> {
+        self.network.lineage(id)
+    }
+    /// snapshot records the entire state of the knowledge network.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::of(&self.network.net.borrow())
+    }
+    /// restore replaces the knowledge network with the one recorded in a
+    /// Snapshot. Every item's mechanism must be registered or already
+    /// present in the current network.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), LoadError> {
+        let net = snapshot.restore(&self.known_mechs())?;
+        self.replace_net(net);
+        Ok(())
+    }
+    /// `journal_to` records every subsequent change to the knowledge
+    /// network as a line of JSON, starting with a snapshot of the current
+    /// network. The journal can be given to .replay().
+    pub fn journal_to<W>(&mut self, w: W)
+        where W: Write + 'static
+    {
+        let mut journal = Journal::new(Box::new(w));
+        journal.record(&Event::Snapshot { network: self.snapshot() });
+        self.observe(journal);
+    }
+    /// observe adds an Observer to be notified of every subsequent event
+    /// in the knowledge network: orienting, growing, evicting, counting,
+    /// the creation of epochs, and the start and end of iterations. If the
+    /// network is replaced, as by .restore(), observers are given a
+    /// snapshot of the new network.
+    pub fn observe<O>(&mut self, observer: O)
+        where O: Observer + 'static
+    {
+        self.network.net.borrow_mut().observers.add(Box::new(observer));
+    }
+    /// replay replaces the knowledge network with the one reconstructed
+    /// from a journal, as written with .journal_to().
+    pub fn replay<R>(&mut self, r: R) -> Result<(), LoadError>
+        where R: BufRead
+    {
+        let net = journal::replay(r, &self.known_mechs())?;
+        self.replace_net(net);
+        Ok(())
+    }
+    /// `known_mechs` gives the names of registered mechanisms and the
+    /// mechanisms of items in the current network.
+    fn known_mechs(&self) -> Vec<&'static str> {
+        let mut mechs = self.network.mechs();
+        mechs.extend(self.reg.reg.iter().map(|r| r.name));
+        mechs
+    }
+    /// `replace_net` swaps in a new Net, carrying over its policy, reported
+    /// progress, and observers.
+    fn replace_net(&mut self, mut net: Net<T>) {
+        {
+            let mut old = self.network.net.borrow_mut();
+            mem::swap(&mut net.policy, &mut old.policy);
+            mem::swap(&mut net.progress, &mut old.progress);
+            mem::swap(&mut net.observers, &mut old.observers);
+        }
+        self.network = Network { net: Rc::new(RefCell::new(net)) };
+        let snapshot = self.snapshot();
+        self.network.net.borrow_mut().notify(|_| Event::Snapshot { network: snapshot });
+    }
+    /// save writes a JSON snapshot of the knowledge network.
+    pub fn save<W>(&self, w: &mut W) -> serde_json::Result<()>
+        where W: Write
+    {
+        serde_json::to_writer(w, &self.snapshot())
+    }
+    /// load replaces the knowledge network with one from a JSON snapshot,
+    /// as written by .save().
+    pub fn load<R>(&mut self, r: R) -> Result<(), LoadError>
+        where R: Read
+    {
+        let snapshot = serde_json::from_reader(r)?;
+        self.restore(snapshot)
     }
     /// dot writes the network in the graphviz DOT language.
     pub fn dot<W>(&self, w: &mut W) -> ::std::io::Result<()>
@@ -461,3 +1295,758 @@ impl<'a> Skn<'a> {
         self.network.dot(w)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::cmp::min;
+    use std::error::Error;
+    use std::collections::BTreeSet;
+    use std::panic;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+
+    use tempdir::TempDir;
+
+    use super::{Context, Event, Eviction, Item, LoadError, Mechanism, OnFailure, Schedule, Skn, Stop};
+    use super::strategy::{Ablation, Decay};
+    use super::strategy::{Ball, PageRank, RandomWalk, Preferential, Fixed, WholeContext};
+
+    /// `steady_mech` accesses every item in its context and grows on every
+    /// other iteration, so that all of the network's randomness comes from
+    /// its seed.
+    fn steady_mech(ctx: Context, i: u64) {
+        for (id, _, _) in ctx.get() {
+            ctx.add_item_count(id, (id as u64 + i) % 5);
+        }
+        if i % 2 == 0 {
+            ctx.grow(format!("{}", i));
+        }
+    }
+
+    fn adjacency(skn: &Skn) -> Vec<BTreeSet<usize>> {
+        let net = skn.network.net.borrow();
+        net.graph.values().map(|item| item.adj.clone()).collect()
+    }
+
+    fn seeded_skn(iterations: u64, seed: u64) -> Skn<'static> {
+        let embryo = vec![("steady", String::from("a")),
+                          ("steady", String::from("b")),
+                          ("steady", String::from("c"))];
+        let mut skn = Skn::with_seed(embryo, iterations, seed);
+        skn.register("steady", &steady_mech);
+        skn
+    }
+
+    fn seeded_run(seed: u64) -> Skn<'static> {
+        let skn = seeded_skn(16, seed);
+        skn.run();
+        skn
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let skn = seeded_run(7);
+        let mut saved = Vec::new();
+        skn.save(&mut saved).unwrap();
+
+        let mut restored = seeded_skn(16, 0);
+        restored.load(saved.as_slice()).unwrap();
+        assert_eq!(skn.snapshot(), restored.snapshot());
+    }
+
+    #[test]
+    fn snapshot_unknown_mechanism() {
+        let mut saved = Vec::new();
+        seeded_skn(1, 7).save(&mut saved).unwrap();
+
+        let embryo = vec![("other", String::from("a"))];
+        let mut restored: Skn = Skn::with_seed(embryo, 1, 7);
+        match restored.load(saved.as_slice()) {
+            Err(LoadError::UnknownMechanism(ref name)) if name == "steady" => (),
+            other => panic!("expected an unknown mechanism, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn seed_reproduces_network() {
+        assert_eq!(seeded_run(7).snapshot(), seeded_run(7).snapshot());
+        assert_ne!(adjacency(&seeded_run(7)), adjacency(&seeded_run(8)));
+    }
+
+    #[test]
+    fn seeded_topology() {
+        let adj: Vec<Vec<usize>> = adjacency(&seeded_run(7))
+            .into_iter()
+            .map(|a| a.into_iter().collect())
+            .collect();
+        let expected: Vec<Vec<usize>> = vec![vec![1, 2],
+                                             vec![0, 2, 3, 9],
+                                             vec![0, 1],
+                                             vec![1, 4, 5, 7, 8, 9],
+                                             vec![3],
+                                             vec![3, 6],
+                                             vec![5],
+                                             vec![3],
+                                             vec![3, 9],
+                                             vec![1, 3, 8, 10],
+                                             vec![9]];
+        assert_eq!(adj, expected);
+    }
+
+    #[test]
+    fn checkpoint_resume() {
+        let tmp_dir = TempDir::new("skn").unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+        let mut first = seeded_skn(6, 7);
+        first.checkpoint_to(&path);
+        first.run();
+
+        let seen = RefCell::new(Vec::new());
+        let recorder = |ctx: Context, i: u64| {
+            seen.borrow_mut().push(i);
+            steady_mech(ctx, i)
+        };
+        let embryo = vec![("steady", String::from("other"))];
+        let mut resumed = Skn::with_seed(embryo, 8, 0);
+        resumed.register("steady", &recorder);
+        resumed.resume_from(&path).unwrap();
+        assert_eq!(first.snapshot(), resumed.snapshot());
+        resumed.run();
+        assert_eq!(*seen.borrow(), vec![7, 8]);
+        let whole = seeded_skn(8, 7);
+        whole.run();
+        assert_eq!(whole.snapshot(), resumed.snapshot());
+    }
+
+    #[test]
+    fn seeded_resume_matches() {
+        let tmp_dir = TempDir::new("skn").unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+        let mut first = seeded_skn(9, 7);
+        first.checkpoint_to(&path);
+        first.run();
+
+        let mut resumed = seeded_skn(16, 0);
+        resumed.resume_from(&path).unwrap();
+        resumed.run();
+        assert_eq!(seeded_run(7).snapshot(), resumed.snapshot());
+    }
+
+    #[test]
+    fn journal_replay() {
+        let tmp_dir = TempDir::new("skn").unwrap();
+        let path = tmp_dir.path().join("journal.jsonl");
+        let mut skn = seeded_skn(10, 7);
+        skn.journal_to(File::create(&path).unwrap());
+        skn.run();
+
+        let mut replayed = seeded_skn(10, 0);
+        replayed.replay(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert_eq!(skn.snapshot(), replayed.snapshot());
+    }
+
+    #[test]
+    fn journal_after_resume() {
+        let tmp_dir = TempDir::new("skn").unwrap();
+        let checkpoint = tmp_dir.path().join("checkpoint.json");
+        let journal = tmp_dir.path().join("journal.jsonl");
+        let mut first = seeded_skn(9, 7);
+        first.checkpoint_to(&checkpoint);
+        first.run();
+
+        // the journal opens with a network that already has counts.
+        let mut resumed = seeded_skn(16, 0);
+        resumed.resume_from(&checkpoint).unwrap();
+        assert!(resumed.network.net.borrow().graph.values().any(|item| !item.counts.is_empty()));
+        resumed.journal_to(File::create(&journal).unwrap());
+        resumed.run();
+
+        let mut replayed = seeded_skn(16, 0);
+        replayed.replay(BufReader::new(File::open(&journal).unwrap())).unwrap();
+        assert_eq!(resumed.snapshot(), replayed.snapshot());
+    }
+
+    #[test]
+    fn context_strategies() {
+        let contexts = |skn: &Skn| -> Vec<(usize, BTreeSet<usize>)> {
+            let net = skn.network.net.borrow();
+            net.epochs.iter().map(|(o, cx, _)| (*o, cx.clone())).collect()
+        };
+        let as_sets = |expected: Vec<(usize, Vec<usize>)>| -> Vec<(usize, BTreeSet<usize>)> {
+            expected.into_iter().map(|(o, cx)| (o, cx.into_iter().collect())).collect()
+        };
+        let mut pagerank = seeded_skn(16, 7);
+        pagerank.set_context_strategy(PageRank::default());
+        pagerank.run();
+        assert_eq!(contexts(&pagerank),
+                   as_sets(vec![(0, vec![0, 1, 2]),
+                                (3, vec![0, 1, 3]),
+                                (4, vec![1, 3, 4]),
+                                (5, vec![1, 3, 5]),
+                                (6, vec![3, 5, 6]),
+                                (7, vec![1, 3, 7]),
+                                (8, vec![1, 3, 8]),
+                                (9, vec![1, 3, 9]),
+                                (10, vec![1, 3, 9, 10])]));
+        let mut walk = seeded_skn(16, 7);
+        walk.set_context_strategy(RandomWalk::default());
+        walk.run();
+        assert_eq!(contexts(&walk),
+                   as_sets(vec![(0, vec![0, 1, 2]),
+                                (3, vec![1, 2, 3]),
+                                (4, vec![1, 3, 4]),
+                                (5, vec![3, 4, 5]),
+                                (6, vec![3, 5, 6]),
+                                (7, vec![3, 5, 7]),
+                                (8, vec![5, 7, 8]),
+                                (9, vec![3, 5, 9]),
+                                (10, vec![5, 8, 9, 10])]));
+
+        // a one-hop ball never leaves the origin's neighborhood.
+        let mut ball = seeded_skn(16, 7);
+        ball.set_context_strategy(Ball { hops: 1 });
+        ball.run();
+        let net = ball.network.net.borrow();
+        for (origin, ctx, _) in net.epochs.iter().skip(1) {
+            let mut hood = net.graph[origin].adj.clone();
+            hood.insert(*origin);
+            assert!(ctx.is_subset(&hood));
+        }
+    }
+
+    #[test]
+    fn attachment_strategies() {
+        let mut preferential = seeded_skn(16, 7);
+        preferential.set_attachment_strategy(Preferential { m: 2 });
+        let mut fixed = seeded_skn(16, 7);
+        fixed.set_attachment_strategy(Fixed { m: 2 });
+        for skn in &[preferential, fixed] {
+            skn.run();
+            let net = skn.network.net.borrow();
+            assert!(net.graph.len() > 3);
+            assert!(net.graph.values().all(|item| item.adj.len() >= 2));
+        }
+
+        // with room for one item, each is grown into an empty network.
+        let mut alone = seeded_skn(8, 7);
+        alone.set_max_size(1);
+        alone.set_attachment_strategy(Fixed { m: 2 });
+        alone.run();
+        {
+            let net = alone.network.net.borrow();
+            assert_eq!(net.graph.len(), 1);
+            assert!(net.graph.values().all(|item| item.adj.is_empty() && item.provenance.antecedents.is_empty()));
+        }
+
+        // m is at least one, so that every new item is connected.
+        let mut none = seeded_skn(16, 7);
+        none.set_attachment_strategy(Preferential { m: 0 });
+        none.run();
+        let net = none.network.net.borrow();
+        assert!(net.graph.len() > 3);
+        assert!(net.graph.values().all(|item| !item.adj.is_empty()));
+    }
+
+    #[test]
+    fn whole_context_attachment() {
+        // the new item is connected to the context it was grown from, even
+        // after the mechanism has oriented elsewhere.
+        let grown = Rc::new(RefCell::new(Vec::new()));
+        let grown_by_mech = grown.clone();
+        let wander_mech = move |ctx: Context, i: u64| {
+            let items: BTreeSet<usize> = ctx.get().into_iter().map(|(id, _, _)| id).collect();
+            ctx.orient(0);
+            let id = ctx.grow(format!("{}", i));
+            grown_by_mech.borrow_mut().push((id, items));
+        };
+        let embryo = vec![("wander", String::from("a")),
+                          ("wander", String::from("b")),
+                          ("wander", String::from("c"))];
+        let mut skn = Skn::with_seed(embryo, 8, 7);
+        skn.set_attachment_strategy(WholeContext);
+        skn.register("wander", &wander_mech);
+        skn.run();
+        let net = skn.network.net.borrow();
+        assert_eq!(grown.borrow().len(), 8);
+        for (id, items) in grown.borrow().iter() {
+            assert_eq!(&net.graph[id].adj.iter().filter(|&&o| o < *id).cloned().collect::<BTreeSet<_>>(),
+                       items);
+        }
+    }
+
+    #[test]
+    fn eviction_bounds_network() {
+        for &eviction in &[Eviction::LeastRecentlyAccessed,
+                           Eviction::LowestCount,
+                           Eviction::LowestDegree] {
+            let tmp_dir = TempDir::new("skn").unwrap();
+            let path = tmp_dir.path().join("journal.jsonl");
+            let mut skn = seeded_skn(40, 7);
+            skn.set_max_size(6);
+            skn.set_eviction(eviction);
+            skn.journal_to(File::create(&path).unwrap());
+            skn.run();
+            {
+                let net = skn.network.net.borrow();
+                assert_eq!(net.graph.len(), 6);
+                assert_eq!(net.next_id, 23);
+                for item in net.graph.values() {
+                    assert!(item.adj.iter().all(|o| net.graph.contains_key(o)));
+                }
+                for (_, cx, ru) in &net.epochs {
+                    assert!(cx.union(ru).all(|o| net.graph.contains_key(o)));
+                }
+            }
+
+            let mut replayed = seeded_skn(40, 0);
+            replayed.replay(BufReader::new(File::open(&path).unwrap())).unwrap();
+            assert_eq!(skn.snapshot(), replayed.snapshot());
+        }
+    }
+
+    #[test]
+    fn decayed_popularity() {
+        let mut hammered = Item::new("steady", BTreeSet::new(), 0);
+        hammered.add_count(0, 100);
+        let mut steady = Item::new("steady", BTreeSet::new(), 1);
+        for epoch in 20..30 {
+            steady.add_count(epoch, 3);
+        }
+        let window = Decay::Window;
+        assert!(window.popularity(&hammered, 0, 30) > window.popularity(&steady, 0, 30));
+        let decay = Decay::HalfLife(5f64);
+        assert!(decay.popularity(&hammered, 0, 30) < decay.popularity(&steady, 0, 30));
+        assert_eq!(decay.popularity(&hammered, 0, 5), 50f64);
+    }
+
+    #[test]
+    fn invalid_half_life() {
+        for &half_life in &[0f64, -5f64, f64::NAN, f64::INFINITY] {
+            let set = panic::catch_unwind(|| seeded_skn(1, 7).set_decay(Decay::HalfLife(half_life)));
+            assert!(set.is_err(), "accepted half-life {}", half_life);
+        }
+    }
+
+    #[test]
+    fn co_access_weights_edges() {
+        let pair_mech = |ctx: Context, _: u64| {
+            ctx.add_item_count(0, 1);
+            ctx.add_item_count(1, 1);
+            ctx.orient(0);
+        };
+        let embryo = vec![("pair", String::from("a")),
+                          ("pair", String::from("b")),
+                          ("pair", String::from("c"))];
+        let mut skn = Skn::with_seed(embryo, 5, 7);
+        skn.register("pair", &pair_mech);
+        skn.run();
+        {
+            let net = skn.network.net.borrow();
+            assert_eq!(net.graph[&0].weight(1), 5f64 + 1f64);
+            assert_eq!(net.graph[&1].weight(0), 5f64 + 1f64);
+            assert_eq!(net.graph[&0].weight(2), 1f64);
+        }
+        let mut dot = Vec::new();
+        skn.dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("N0 -- N1 [label=\"6.00\""));
+    }
+
+    #[test]
+    fn last_epoch_weights_edges() {
+        let pair_mech = |ctx: Context, _: u64| {
+            ctx.add_item_count(0, 1);
+            ctx.add_item_count(1, 1);
+        };
+        let embryo = vec![("pair", String::from("a")),
+                          ("pair", String::from("b")),
+                          ("pair", String::from("c"))];
+        let mut skn = Skn::with_seed(embryo, 5, 7);
+        skn.register("pair", &pair_mech);
+        skn.run();
+        let net = skn.network.net.borrow();
+        assert_eq!(net.graph[&0].weight(1), 1f64 + 1f64);
+        assert_eq!(net.graph[&0].weight(2), 1f64);
+        assert_eq!(net.epochs.len(), 2);
+        assert!(net.epochs[1].2.is_empty());
+    }
+
+    #[test]
+    fn grown_items_have_provenance() {
+        let skn = seeded_run(7);
+        let net = skn.network.net.borrow();
+        for (&id, item) in net.graph.range(3..) {
+            let provenance = skn.provenance(id).unwrap();
+            assert_eq!(provenance.mech, "steady");
+            assert_eq!(provenance.iteration % 2, 0);
+            assert_eq!(net.epochs[provenance.epoch].0, id);
+            assert!(!provenance.parents.is_empty());
+            assert!(provenance.parents.iter().all(|p| provenance.antecedents.contains_key(p)));
+            assert!(provenance.parents.is_subset(&item.adj));
+            let total: f64 = provenance.antecedents.values().sum();
+            assert!((total - 1f64).abs() < 1e-9);
+        }
+        assert_eq!(skn.provenance(0).unwrap().iteration, 0);
+
+        let latest = *net.graph.keys().next_back().unwrap();
+        let lineage = skn.lineage(latest);
+        assert_eq!(lineage[0].0, latest);
+        assert!(lineage.iter().any(|&(id, _)| id < 3));
+    }
+
+    #[test]
+    fn network_stats() {
+        let tmp_dir = TempDir::new("skn").unwrap();
+        let path = tmp_dir.path().join("stats.jsonl");
+        let mut skn = seeded_skn(16, 7);
+        skn.stats_to(File::create(&path).unwrap());
+        skn.run();
+
+        let stats = skn.stats();
+        assert_eq!(stats.nodes, 11);
+        assert_eq!(stats.edges, 13);
+        assert_eq!(stats.degrees.into_iter().collect::<Vec<_>>(),
+                   vec![(1, 4), (2, 4), (4, 2), (6, 1)]);
+        assert_eq!(stats.max_degree, 6);
+        assert!(stats.gamma.unwrap() > 1f64);
+        assert!((stats.clustering - 3.8 / 11f64).abs() < 1e-9);
+        assert_eq!(stats.components, 1);
+        assert_eq!(stats.diameter, 4);
+        assert_eq!(skn.snapshot().stats(), skn.stats());
+
+        let lines: Vec<String> = BufReader::new(File::open(&path).unwrap())
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(lines.len(), 16);
+        assert!(lines[15].starts_with("{\"iteration\":16,\"nodes\":11,"));
+    }
+
+    #[test]
+    fn context_origin() {
+        let embryo = vec![("origin", String::from("a")), ("origin", String::from("b"))];
+        let mut skn = Skn::with_seed(embryo, 1, 7);
+        let mech = |ctx: Context, _| {
+            assert_eq!(ctx.origin(), 0);
+            assert_eq!(ctx.size(), 2);
+            let id = ctx.grow(String::from("c"));
+            assert_eq!(ctx.update().origin(), id);
+            assert_eq!(ctx.size(), 3);
+            ctx.orient(1);
+            assert_eq!(ctx.update().origin(), 1);
+            let next = ctx.grow(String::from("d"));
+            assert_eq!(ctx.grown_from(next), Some(1));
+            assert_eq!(ctx.grown_from(id), Some(0));
+            assert_eq!(ctx.grown_from(1), None);
+        };
+        skn.register("origin", mech);
+        skn.run();
+    }
+
+    #[test]
+    fn ablated_contexts() {
+        let ids = |contents: Vec<(usize, &'static str, Rc<String>)>| -> BTreeSet<usize> {
+            contents.into_iter().map(|(id, _, _)| id).collect()
+        };
+        let mut skn = seeded_run(7);
+        let ctx = skn.network.context("steady", 17);
+        let (chosen, explored) = (ids(ctx.get()), ids(ctx.explore()));
+        assert_eq!(ctx.ablation(), None);
+
+        skn.set_ablation(Ablation::Whole);
+        let ctx = skn.network.context("steady", 17);
+        assert_eq!(ctx.ablation(), Some(Ablation::Whole));
+        assert_eq!(ctx.get().len(), 11);
+        assert_eq!(ids(ctx.explore()), explored);
+
+        skn.set_ablation(Ablation::Embryo);
+        let ctx = skn.network.context("steady", 17);
+        assert_eq!(ids(ctx.get()), (0..3).collect());
+
+        skn.set_ablation(Ablation::Random);
+        let ctx = skn.network.context("steady", 17);
+        let random = ids(ctx.get());
+        assert_eq!(random.len(), chosen.len());
+        assert_eq!(ids(ctx.update().get()), random);
+        assert_eq!(ids(ctx.explore()), explored);
+    }
+
+    #[test]
+    fn explore_depth() {
+        let skn = seeded_run(7);
+        let ctx = skn.network.context("steady", 17);
+        let ids = |contents: Vec<(usize, &'static str, Rc<String>)>| -> Vec<usize> {
+            contents.into_iter().map(|(id, _, _)| id).collect()
+        };
+        assert_eq!(ids(ctx.explore_within(0)), ids(ctx.get()));
+        assert_eq!(ids(ctx.explore_within(1)), ids(ctx.explore()));
+        assert_eq!(ids(ctx.explore_within(1)), vec![0, 1, 2, 3, 4, 5, 7, 8, 9, 10]);
+        assert_eq!(ctx.explore_within(2).len(), 11);
+
+        let ranked: Vec<(usize, usize)> = ctx.explore_ranked(2)
+            .into_iter()
+            .map(|(id, _, _, dist)| (id, dist))
+            .collect();
+        assert_eq!(ranked.len(), 11);
+        let mut nearest: Vec<usize> = ranked[..4].iter().map(|&(id, _)| id).collect();
+        nearest.sort();
+        assert_eq!(nearest, vec![1, 3, 9, 10]);
+        assert!(ranked.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(ranked[10], (6, 2));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pair {
+        n: u64,
+        name: String,
+    }
+
+    #[test]
+    fn typed_payloads() {
+        let pair_mech = |ctx: Context<Pair>, i: u64| {
+            let total: u64 = ctx.get().iter().map(|(_, _, pair)| pair.n).sum();
+            ctx.grow(Pair {
+                         n: total + i,
+                         name: format!("pair {}", i),
+                     });
+        };
+        let embryo = vec![("pair", Pair { n: 1, name: String::from("a") }),
+                          ("pair", Pair { n: 2, name: String::from("b") })];
+        let mut skn = Skn::with_seed(embryo, 4, 7);
+        skn.register("pair", &pair_mech);
+        skn.run();
+
+        let mut saved = Vec::new();
+        skn.save(&mut saved).unwrap();
+        let embryo = vec![("pair", Pair { n: 0, name: String::new() })];
+        let mut loaded: Skn<Pair> = Skn::with_seed(embryo, 4, 8);
+        loaded.register("pair", &pair_mech);
+        loaded.load(&saved[..]).unwrap();
+        assert_eq!(skn.snapshot(), loaded.snapshot());
+
+        // data of the wrong type is rejected when read
+        let mut untyped = Vec::new();
+        seeded_run(7).save(&mut untyped).unwrap();
+        let mismatched = String::from_utf8(untyped).unwrap().replace("steady", "pair");
+        match loaded.load(mismatched.as_bytes()) {
+            Err(LoadError::Parse(_)) => (),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    struct Lifecycle<'a> {
+        log: &'a RefCell<Vec<String>>,
+        steps: u64,
+    }
+
+    impl<'a> Mechanism for Lifecycle<'a> {
+        fn init(&mut self) {
+            self.log.borrow_mut().push(String::from("init"));
+        }
+        fn step(&mut self, ctx: Context, iteration: u64) -> Result<(), Box<dyn Error>> {
+            self.steps += 1;
+            self.log.borrow_mut().push(format!("step {} of {}", self.steps, iteration));
+            ctx.grow(format!("{}", iteration));
+            Ok(())
+        }
+        fn after_iteration(&mut self, ctx: Context, iteration: u64) {
+            self.log.borrow_mut().push(format!("after {} at {}", iteration, ctx.origin()));
+        }
+        fn finish(&mut self) {
+            self.log.borrow_mut().push(String::from("finish"));
+        }
+    }
+
+    #[test]
+    fn mechanism_lifecycle() {
+        let log = RefCell::new(Vec::new());
+        let embryo = vec![("lifecycle", String::from("a"))];
+        let mut skn = Skn::with_seed(embryo, 2, 7);
+        skn.register("lifecycle", Lifecycle { log: &log, steps: 0 });
+        skn.register("closure", |_: Context, i| log.borrow_mut().push(format!("closure {}", i)));
+        skn.run();
+        assert_eq!(*log.borrow(),
+                   vec!["init", "step 1 of 1", "closure 1", "after 1 at 1", "step 2 of 2",
+                        "closure 2", "after 2 at 2", "finish"]);
+    }
+
+    /// Tally counts its steps, keeping the count in checkpoints.
+    struct Tally {
+        steps: u64,
+        total: Rc<Cell<u64>>,
+    }
+
+    impl Mechanism for Tally {
+        fn step(&mut self, _: Context, _: u64) -> Result<(), Box<dyn Error>> {
+            self.steps += 1;
+            Ok(())
+        }
+        fn finish(&mut self) {
+            self.total.set(self.steps);
+        }
+        fn save(&self) -> Option<serde_json::Value> {
+            Some(serde_json::Value::from(self.steps))
+        }
+        fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn Error>> {
+            self.steps = state.as_u64().ok_or("steps must be a number")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn mechanism_state_resumes() {
+        let tmp_dir = TempDir::new("skn").unwrap();
+        let path = tmp_dir.path().join("checkpoint.json");
+        let total = Rc::new(Cell::new(0));
+        let mut first = seeded_skn(5, 7);
+        first.register("tally", Tally { steps: 0, total: total.clone() });
+        first.checkpoint_to(&path);
+        first.run();
+        assert_eq!(total.get(), 5);
+
+        let mut resumed = seeded_skn(9, 7);
+        resumed.register("tally", Tally { steps: 0, total: total.clone() });
+        resumed.resume_from(&path).unwrap();
+        resumed.run();
+        assert_eq!(total.get(), 9);
+    }
+
+    #[test]
+    fn mechanism_schedules() {
+        let log = RefCell::new(Vec::new());
+        let grower = |ctx: Context, i| {
+            log.borrow_mut().push(format!("grower {}", i));
+            ctx.grow(format!("{}", i));
+        };
+        let follower = |_: Context, i| log.borrow_mut().push(format!("follower {}", i));
+        let first = |_: Context, i| log.borrow_mut().push(format!("first {}", i));
+        let embryo = vec![("grower", String::from("a"))];
+        let mut skn = Skn::with_seed(embryo, 6, 7);
+        skn.register_with_schedule("grower",
+                                   grower,
+                                   Schedule {
+                                       start: 2,
+                                       every: 2,
+                                       ..Schedule::default()
+                                   });
+        skn.register_with_schedule("follower",
+                                   follower,
+                                   Schedule {
+                                       after_growth_by: Some("grower"),
+                                       ..Schedule::default()
+                                   });
+        skn.register_with_schedule("first",
+                                   first,
+                                   Schedule {
+                                       until: Some(3),
+                                       priority: 1,
+                                       ..Schedule::default()
+                                   });
+        skn.run();
+        assert_eq!(*log.borrow(),
+                   vec!["first 1", "first 2", "grower 2", "follower 2", "first 3", "grower 4",
+                        "follower 4", "grower 6", "follower 6"]);
+    }
+
+    #[test]
+    fn stopping_criteria() {
+        let stopped = |stop: Stop| {
+            let mech = |ctx: Context, i: u64| {
+                ctx.report([0.1, 0.5, 0.5, 0.4][min(i as usize, 4) - 1]);
+                if i <= 2 {
+                    ctx.grow(format!("{}", i));
+                }
+            };
+            let embryo = vec![("stop", String::from("a")), ("stop", String::from("b"))];
+            let mut skn = Skn::with_seed(embryo, 10, 7);
+            skn.register("stop", mech);
+            skn.stop_when(stop);
+            let stopped = skn.run().map(|(t, _)| t);
+            assert_eq!(skn.progress("stop").len() as u64, stopped.unwrap_or(10));
+            stopped
+        };
+        assert_eq!(stopped(Stop::NoGrowth(2)), Some(4));
+        assert_eq!(stopped(Stop::Size(4)), Some(2));
+        assert_eq!(stopped(Stop::Plateau {
+                               mech: "stop",
+                               window: 2,
+                               tolerance: 0f64,
+                           }),
+                   Some(4));
+        assert_eq!(stopped(Stop::WallClock(Duration::from_secs(0))), Some(1));
+        assert_eq!(stopped(Stop::Size(100)), None);
+    }
+
+    #[test]
+    fn failure_policies() {
+        let run = |on_failure: OnFailure| -> (Vec<String>, Skn<'static>) {
+            let steps = Rc::new(RefCell::new(Vec::new()));
+            let (flaky_steps, steady_steps) = (steps.clone(), steps.clone());
+            // fails in even iterations, on every attempt.
+            let flaky = move |_: Context, i: u64| -> Result<(), String> {
+                flaky_steps.borrow_mut().push(format!("flaky {}", i));
+                if i % 2 == 0 { Err(format!("bad {}", i)) } else { Ok(()) }
+            };
+            let steady = move |_: Context, i: u64| {
+                steady_steps.borrow_mut().push(format!("steady {}", i));
+            };
+            let mut skn = Skn::with_seed(vec![("flaky", String::from("a"))], 2, 7);
+            skn.register_with_schedule("flaky",
+                                       flaky,
+                                       Schedule {
+                                           priority: 1,
+                                           ..Schedule::default()
+                                       });
+            skn.register("steady", steady);
+            skn.on_failure(on_failure);
+            skn.run();
+            let steps = steps.borrow().clone();
+            (steps, skn)
+        };
+
+        let (steps, skn) = run(OnFailure::Log);
+        assert_eq!(steps, vec!["flaky 1", "steady 1", "flaky 2", "steady 2"]);
+        assert_eq!(skn.failures(), vec![(2, "flaky", String::from("bad 2"))]);
+
+        let (steps, _) = run(OnFailure::Skip);
+        assert_eq!(steps, vec!["flaky 1", "steady 1", "flaky 2"]);
+
+        let (steps, skn) = run(OnFailure::Retry(2));
+        assert_eq!(steps, vec!["flaky 1", "steady 1", "flaky 2", "flaky 2", "flaky 2", "steady 2"]);
+        assert_eq!(skn.failures().len(), 3);
+    }
+
+    #[test]
+    fn observers_see_events() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = seen.clone();
+        let observer = move |event: &Event| {
+            let line = match *event {
+                Event::IterationStart { iteration } => format!("start {}", iteration),
+                Event::IterationEnd { iteration } => format!("end {}", iteration),
+                Event::Orient { id, .. } => format!("orient {}", id),
+                Event::Epoch { epoch, origin } => format!("epoch {} at {}", epoch, origin),
+                Event::Grow { id, ref mech, ref data, .. } => format!("grow {} by {} {}", id, mech, data),
+                Event::Count { id, count, .. } => format!("count {} {}", id, count),
+                Event::Evict { id, .. } => format!("evict {}", id),
+                Event::Snapshot { .. } => String::from("snapshot"),
+                Event::Failure { ref reason, .. } => format!("failure {}", reason),
+            };
+            log.borrow_mut().push(line);
+        };
+        let mech = |ctx: Context, i: u64| {
+            let id = ctx.grow(format!("{}", i));
+            ctx.update().add_item_count(id, 2);
+        };
+        let mut skn = Skn::with_seed(vec![("observed", String::from("a"))], 1, 7);
+        skn.register("observed", mech);
+        skn.observe(observer);
+        skn.run();
+        assert_eq!(*seen.borrow(),
+                   vec!["start 1", "grow 1 by observed \"1\"", "orient 1", "epoch 1 at 1",
+                        "count 1 2", "end 1", "orient 1", "epoch 2 at 1"]);
+    }
+}
@@ -0,0 +1,103 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::collections::BTreeSet;
+
+use super::Net;
+
+/// Eviction is the policy for choosing which item to retire when the
+/// network reaches its maximum size. Items in the latest context are only
+/// retired if there is nothing else to retire, and ties go to the oldest
+/// item.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Eviction {
+    /// retire the item that has gone longest without being in a context
+    /// or accessed.
+    #[default]
+    LeastRecentlyAccessed,
+    /// retire the item with the fewest accesses over all epochs.
+    LowestCount,
+    /// retire the item with the fewest neighbors.
+    LowestDegree,
+}
+
+impl Eviction {
+    /// victim chooses the item that should be retired from the network.
+    pub(super) fn victim<T>(&self, net: &Net<T>) -> Option<usize> {
+        let current = &net.epochs[net.epochs.len() - 1].1;
+        let candidates: Vec<usize> = if net.graph.keys().all(|id| current.contains(id)) {
+            net.graph.keys().cloned().collect()
+        } else {
+            net.graph.keys().filter(|id| !current.contains(id)).cloned().collect()
+        };
+        match *self {
+            Eviction::LeastRecentlyAccessed => {
+                candidates.into_iter().min_by_key(|&id| (last_touched(net, id), id))
+            }
+            Eviction::LowestCount => {
+                candidates.into_iter().min_by_key(|&id| (net.graph[&id].recent_count(0), id))
+            }
+            Eviction::LowestDegree => {
+                candidates.into_iter().min_by_key(|&id| (net.graph[&id].adj.len(), id))
+            }
+        }
+    }
+}
+
+/// `last_touched` is the latest epoch in which the item was in the context
+/// or accessed, or zero if it never was.
+fn last_touched<T>(net: &Net<T>, id: usize) -> usize {
+    net.epochs
+        .iter()
+        .rposition(|(_, cx, ru)| cx.contains(&id) || ru.contains(&id))
+        .unwrap_or(0)
+}
+
+/// retire removes an item from the network. Neighbors that would be left
+/// without any neighbors are instead connected to the retired item's most
+/// connected remaining neighbor, and the item is removed from every
+/// epoch's context and accesses.
+pub(super) fn retire<T>(net: &mut Net<T>, id: usize) {
+    let item = match net.graph.remove(&id) {
+        Some(item) => item,
+        None => return,
+    };
+    net.data.remove(&id);
+    for oid in &item.adj {
+        net.graph.get_mut(oid).unwrap().unlink(id);
+    }
+    let hub = item.adj
+        .iter()
+        .max_by_key(|&&oid| (net.graph[&oid].adj.len(), oid))
+        .cloned();
+    if let Some(hub) = hub {
+        let orphans: BTreeSet<usize> = item.adj
+            .iter()
+            .filter(|&&oid| oid != hub && net.graph[&oid].adj.is_empty())
+            .cloned()
+            .collect();
+        for &oid in &orphans {
+            net.graph.get_mut(&oid).unwrap().link(hub);
+            net.graph.get_mut(&hub).unwrap().link(oid);
+        }
+    }
+    for epoch in &mut net.epochs {
+        epoch.1.remove(&id);
+        epoch.2.remove(&id);
+    }
+}
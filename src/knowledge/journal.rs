@@ -0,0 +1,252 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+extern crate serde_json;
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use super::{Cause, Item, Net, Payload, Provenance};
+use super::evict;
+use super::observe::Observer;
+use super::snapshot::{Snapshot, LoadError, int_keys, resolve};
+
+/// Event is something that happened to the knowledge network, as given to
+/// observers and recorded in a journal. In a journal, each is written as one
+/// line of JSON.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// the state of the network when journaling began.
+    Snapshot { network: Snapshot },
+    /// a call to Context::orient and the context it produced, or the end of
+    /// a run closing the latest epoch (by mech "run", with the same context).
+    Orient {
+        epoch: usize,
+        mech: String,
+        iteration: u64,
+        id: usize,
+        context: BTreeSet<usize>,
+    },
+    /// a call to Context::grow_for_mech and the edges of the new item.
+    Grow {
+        epoch: usize,
+        mech: String,
+        iteration: u64,
+        for_mech: String,
+        data: serde_json::Value,
+        id: usize,
+        edges: BTreeSet<usize>,
+        #[serde(default, deserialize_with = "int_keys")]
+        antecedents: BTreeMap<usize, f64>,
+    },
+    /// an item retired to make room for a grown item.
+    Evict {
+        epoch: usize,
+        mech: String,
+        iteration: u64,
+        id: usize,
+    },
+    /// the creation of a new epoch by orienting to (or growing) an item.
+    Epoch { epoch: usize, origin: usize },
+    /// the beginning of an iteration of Skn::run.
+    IterationStart { iteration: u64 },
+    /// the end of an iteration of Skn::run, once every mechanism has
+    /// stepped.
+    IterationEnd { iteration: u64 },
+    /// a step of a mechanism that failed, and why. Under
+    /// `OnFailure::Retry`, there is one for every attempt.
+    Failure {
+        mech: String,
+        iteration: u64,
+        reason: String,
+    },
+    /// a call to Context::add_item_count.
+    Count {
+        epoch: usize,
+        mech: String,
+        iteration: u64,
+        id: usize,
+        count: u64,
+    },
+}
+
+/// Journal writes events to an append-only log.
+pub(super) struct Journal {
+    w: Box<dyn Write>,
+}
+
+impl fmt::Debug for Journal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Journal")
+    }
+}
+
+impl Observer for Journal {
+    fn notify(&mut self, event: &Event) {
+        self.record(event)
+    }
+}
+
+impl Journal {
+    pub fn new(w: Box<dyn Write>) -> Journal {
+        Journal { w }
+    }
+    /// record appends an event to the journal. Failures are reported but
+    /// don't interrupt the network.
+    pub fn record(&mut self, event: &Event) {
+        let result = serde_json::to_writer(&mut self.w, event)
+            .map_err(From::from)
+            .and_then(|_| writeln!(self.w))
+            .and_then(|_| self.w.flush());
+        if let Err(err) = result {
+            eprintln!("could not write journal: {}", err)
+        }
+    }
+}
+
+impl Event {
+    pub(super) fn orient(cause: Cause, epoch: usize, id: usize, context: &BTreeSet<usize>) -> Event {
+        Event::Orient {
+            epoch,
+            mech: String::from(cause.mech),
+            iteration: cause.iteration,
+            id,
+            context: context.clone(),
+        }
+    }
+    pub(super) fn grow<T: Payload>(cause: Cause, epoch: usize, item: &Item, data: &T) -> Event {
+        Event::Grow {
+            epoch,
+            mech: String::from(cause.mech),
+            iteration: cause.iteration,
+            for_mech: String::from(item.mech),
+            data: serde_json::to_value(data).expect("serialize item data"),
+            id: item.id,
+            edges: item.adj.clone(),
+            antecedents: item.provenance.antecedents.clone(),
+        }
+    }
+    pub(super) fn evict(cause: Cause, epoch: usize, id: usize) -> Event {
+        Event::Evict {
+            epoch,
+            mech: String::from(cause.mech),
+            iteration: cause.iteration,
+            id,
+        }
+    }
+    pub(super) fn count(cause: Cause, epoch: usize, id: usize, count: u64) -> Event {
+        Event::Count {
+            epoch,
+            mech: String::from(cause.mech),
+            iteration: cause.iteration,
+            id,
+            count,
+        }
+    }
+}
+
+/// replay reconstructs a Net from a journal, resolving mechanism names
+/// against mechs. The journal must begin with a snapshot event.
+pub(super) fn replay<R, T>(r: R, mechs: &[&'static str]) -> Result<Net<T>, LoadError>
+    where R: BufRead,
+          T: Payload
+{
+    let mut net: Option<Net<T>> = None;
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: Event = serde_json::from_str(&line)?;
+        if let Event::Snapshot { network } = event {
+            net = Some(network.restore(mechs)?);
+            continue;
+        }
+        let net = match net {
+            Some(ref mut net) => net,
+            None => {
+                return Err(LoadError::Malformed(format!("journal line {} precedes snapshot", i + 1)))
+            }
+        };
+        match event {
+            Event::Orient { id, context, .. } => {
+                let mut present = context.iter().chain(Some(&id));
+                if let Some(o) = present.find(|o| !net.graph.contains_key(o)) {
+                    return Err(LoadError::Malformed(format!("orient refers to missing {}", o)));
+                }
+                net.close_epoch();
+                net.epochs.push((id, context, BTreeSet::new()));
+            }
+            Event::Grow { mech: by, iteration, for_mech, data, id, edges, antecedents, .. } => {
+                if id != net.next_id {
+                    return Err(LoadError::Malformed(format!("grew {} out of order", id)));
+                }
+                if let Some(o) = edges.iter().find(|o| !net.graph.contains_key(o)) {
+                    return Err(LoadError::Malformed(format!("grow refers to missing {}", o)));
+                }
+                let mech = resolve(mechs, for_mech)?;
+                let by = resolve(mechs, by)?;
+                let data: T = serde_json::from_value(data)?;
+                for oid in &edges {
+                    net.graph.get_mut(oid).unwrap().link(id);
+                }
+                let mut item = Item::new(mech, edges, id);
+                item.provenance = Provenance {
+                    mech: by,
+                    iteration,
+                    epoch: net.epochs.len(),
+                    parents: item.adj.clone(),
+                    antecedents,
+                };
+                net.graph.insert(id, item);
+                net.data.insert(id, Rc::new(data));
+                net.next_id += 1;
+            }
+            Event::Evict { id, .. } => {
+                if !net.graph.contains_key(&id) {
+                    return Err(LoadError::Malformed(format!("evict refers to missing {}", id)));
+                }
+                evict::retire(net, id);
+            }
+            Event::Count { epoch, id, count, .. } => {
+                if epoch >= net.epochs.len() {
+                    return Err(LoadError::Malformed(format!("count refers to missing epoch {}", epoch)));
+                }
+                // counts for retired items are ignored, as in the network
+                if let Some(item) = net.graph.get_mut(&id) {
+                    item.add_count(epoch, count);
+                    net.epochs[epoch].2.insert(id);
+                }
+            }
+            Event::Epoch { epoch, .. } => {
+                if epoch + 1 != net.epochs.len() {
+                    return Err(LoadError::Malformed(format!("epoch {} out of order", epoch)));
+                }
+            }
+            // these don't change the network
+            Event::IterationStart { .. } |
+            Event::IterationEnd { .. } |
+            Event::Failure { .. } => (),
+            Event::Snapshot { .. } => unreachable!(),
+        }
+    }
+    net.ok_or_else(|| LoadError::Malformed(String::from("journal has no snapshot")))
+}
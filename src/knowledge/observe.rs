@@ -0,0 +1,62 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::fmt;
+
+use super::Event;
+
+/// Observer is notified of everything that happens to a knowledge network,
+/// in the order that it happens. Any function which takes an Event is an
+/// Observer.
+pub trait Observer {
+    fn notify(&mut self, event: &Event);
+}
+
+impl<F> Observer for F
+    where F: FnMut(&Event)
+{
+    fn notify(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Observers are the observers of a Net.
+#[derive(Default)]
+pub(super) struct Observers {
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Observers({})", self.observers.len())
+    }
+}
+
+impl Observers {
+    pub fn add(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+    pub fn notify(&mut self, event: &Event) {
+        for observer in &mut self.observers {
+            observer.notify(event);
+        }
+    }
+}
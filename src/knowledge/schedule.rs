@@ -0,0 +1,113 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::f64;
+use std::time::Duration;
+
+/// Schedule determines the iterations in which a mechanism steps, and its
+/// order among the mechanisms that step in the same iteration. Unspecified
+/// fields can be taken from `Schedule::default()`, which steps in every
+/// iteration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+    /// the first iteration to step in.
+    pub start: u64,
+    /// the last iteration to step in, if the mechanism should stop before
+    /// the others.
+    pub until: Option<u64>,
+    /// step in only every this many iterations, counting from start.
+    pub every: u64,
+    /// mechanisms with higher priority step first within an iteration.
+    /// Mechanisms of equal priority step in the order they were
+    /// registered.
+    pub priority: i64,
+    /// if set, step only if the named mechanism has grown the network
+    /// since this mechanism last stepped.
+    pub after_growth_by: Option<&'static str>,
+}
+
+impl Default for Schedule {
+    fn default() -> Schedule {
+        Schedule {
+            start: 1,
+            until: None,
+            every: 1,
+            priority: 0,
+            after_growth_by: None,
+        }
+    }
+}
+
+impl Schedule {
+    /// due tells whether the iteration is one to step in, before
+    /// considering what other mechanisms have done.
+    pub(super) fn due(&self, iteration: u64) -> bool {
+        iteration >= self.start && self.until.map_or(true, |until| iteration <= until) &&
+        (iteration - self.start) % self.every.max(1) == 0
+    }
+}
+
+/// Stop is a criterion for ending a run before its last iteration. It is
+/// checked at the end of every iteration.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Stop {
+    /// stop once no item has been grown for this many iterations in a row.
+    NoGrowth(u64),
+    /// stop once the progress reported by the named mechanism has not
+    /// improved on its earlier best by more than tolerance over its last
+    /// window reports.
+    Plateau {
+        mech: &'static str,
+        window: usize,
+        tolerance: f64,
+    },
+    /// stop once the run has taken this long.
+    WallClock(Duration),
+    /// stop once the network holds at least this many items.
+    Size(usize),
+}
+
+/// OnFailure is what a run does when a mechanism fails a step.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OnFailure {
+    /// report the failure and carry on with the iteration.
+    #[default]
+    Log,
+    /// report the failure and skip the rest of the iteration: no other
+    /// mechanism steps in it.
+    Skip,
+    /// step the mechanism again, with a fresh Context, up to this many
+    /// times, then report the failure and carry on. Anything the failed
+    /// steps did to the network is kept. Every failed attempt is reported,
+    /// so a mechanism that retries on its own, as ec does with its Limits,
+    /// is better left to that.
+    Retry(u32),
+}
+
+impl Stop {
+    /// plateaued tells whether a series of progress reports has stopped
+    /// improving.
+    pub(super) fn plateaued(reports: &[(u64, f64)], window: usize, tolerance: f64) -> bool {
+        if window == 0 || reports.len() <= window {
+            return false;
+        }
+        let (before, recent) = reports.split_at(reports.len() - window);
+        let best = |r: &[(u64, f64)]| r.iter().map(|&(_, p)| p).fold(f64::NEG_INFINITY, f64::max);
+        best(recent) <= best(before) + tolerance
+    }
+}
@@ -0,0 +1,280 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+extern crate serde;
+extern crate serde_json;
+
+use std::collections::{BTreeSet, BTreeMap};
+use std::error;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use self::serde::{Deserialize, Deserializer};
+use self::serde::de::Error as DeError;
+
+use super::{Item, Net, Payload, Policy, Provenance, seeded_rng};
+use super::stats::Stats;
+use super::observe::Observers;
+
+/// Snapshot is a serializable record of every item, edge, per-epoch access
+/// count and epoch of a knowledge network.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(default)]
+    seed: u64,
+    context_min_size: usize,
+    max_size: usize,
+    #[serde(default)]
+    next_id: usize,
+    items: Vec<ItemSnapshot>,
+    epochs: Vec<EpochSnapshot>,
+}
+
+/// Checkpoint is a Snapshot taken between iterations of an Skn run,
+/// along with the iteration to continue from and the state of every
+/// mechanism that keeps any (see `Mechanism::save`).
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub iteration: u64,
+    pub network: Snapshot,
+    #[serde(default)]
+    pub mechanisms: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ItemSnapshot {
+    id: usize,
+    mech: String,
+    data: serde_json::Value,
+    #[serde(deserialize_with = "int_keys")]
+    counts: BTreeMap<usize, u64>,
+    adj: BTreeSet<usize>,
+    #[serde(default, deserialize_with = "int_keys")]
+    weights: BTreeMap<usize, f64>,
+    #[serde(default)]
+    provenance: Option<ProvenanceSnapshot>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ProvenanceSnapshot {
+    mech: String,
+    iteration: u64,
+    epoch: usize,
+    parents: BTreeSet<usize>,
+    #[serde(deserialize_with = "int_keys")]
+    antecedents: BTreeMap<usize, f64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct EpochSnapshot {
+    origin: usize,
+    context: BTreeSet<usize>,
+    accessed: BTreeSet<usize>,
+}
+
+/// `LoadError` is the reason a Snapshot could not be restored.
+#[derive(Debug)]
+pub enum LoadError {
+    /// the snapshot could not be read.
+    Io(io::Error),
+    /// the snapshot was not valid JSON of the right shape.
+    Parse(serde_json::Error),
+    /// an item was made by a mechanism that isn't known to the Skn.
+    UnknownMechanism(String),
+    /// the snapshot refers to items that it doesn't contain.
+    Malformed(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref err) => write!(f, "read snapshot: {}", err),
+            LoadError::Parse(ref err) => write!(f, "parse snapshot: {}", err),
+            LoadError::UnknownMechanism(ref name) => {
+                write!(f, "snapshot has unknown mechanism {:?}", name)
+            }
+            LoadError::Malformed(ref why) => write!(f, "malformed snapshot: {}", why),
+        }
+    }
+}
+
+impl error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(err: serde_json::Error) -> LoadError {
+        LoadError::Parse(err)
+    }
+}
+
+impl Snapshot {
+    /// of records the current state of a Net.
+    pub(super) fn of<T: Payload>(net: &Net<T>) -> Snapshot {
+        Snapshot {
+            seed: net.seed,
+            context_min_size: net.context_min_size,
+            max_size: net.max_size,
+            next_id: net.next_id,
+            items: net.graph
+                .values()
+                .map(|item| {
+                         ItemSnapshot {
+                             id: item.id,
+                             mech: String::from(item.mech),
+                             data: serde_json::to_value(&*net.data[&item.id])
+                                 .expect("serialize item data"),
+                             counts: item.counts.clone(),
+                             adj: item.adj.clone(),
+                             weights: item.weights.clone(),
+                             provenance: Some(ProvenanceSnapshot {
+                                                  mech: String::from(item.provenance.mech),
+                                                  iteration: item.provenance.iteration,
+                                                  epoch: item.provenance.epoch,
+                                                  parents: item.provenance.parents.clone(),
+                                                  antecedents: item.provenance
+                                                      .antecedents
+                                                      .clone(),
+                                              }),
+                         }
+                     })
+                .collect(),
+            epochs: net.epochs
+                .iter()
+                .map(|(origin, context, accessed)| {
+                         EpochSnapshot {
+                             origin: *origin,
+                             context: context.clone(),
+                             accessed: accessed.clone(),
+                         }
+                     })
+                .collect(),
+        }
+    }
+    /// stats measures the shape of the recorded network.
+    pub fn stats(&self) -> Stats {
+        Stats::of(self.items.iter().map(|item| (item.id, &item.adj)))
+    }
+    /// restore rebuilds a Net from this Snapshot. Mechanism names are
+    /// resolved against the given set of known names.
+    pub(super) fn restore<T: Payload>(self, mechs: &[&'static str]) -> Result<Net<T>, LoadError> {
+        if self.epochs.is_empty() {
+            return Err(LoadError::Malformed(String::from("no epochs")));
+        }
+        let ids: BTreeSet<usize> = self.items.iter().map(|item| item.id).collect();
+        if ids.len() != self.items.len() {
+            return Err(LoadError::Malformed(String::from("duplicate item ids")));
+        }
+        let next_id = ids.iter().next_back().map_or(0, |id| id + 1).max(self.next_id);
+        let mut graph = BTreeMap::new();
+        let mut data = BTreeMap::new();
+        for item in self.items {
+            if let Some(o) = item.adj.iter().find(|o| !ids.contains(o)) {
+                return Err(LoadError::Malformed(format!("item {} adjacent to missing {}", item.id, o)));
+            }
+            if let Some(o) = item.weights.keys().find(|o| !item.adj.contains(o)) {
+                return Err(LoadError::Malformed(format!("item {} weighs missing edge to {}", item.id, o)));
+            }
+            let mech = resolve(mechs, item.mech)?;
+            let provenance = match item.provenance {
+                Some(p) => {
+                    Provenance {
+                        mech: resolve(mechs, p.mech)?,
+                        iteration: p.iteration,
+                        epoch: p.epoch,
+                        parents: p.parents,
+                        antecedents: p.antecedents,
+                    }
+                }
+                // snapshots from before provenance was recorded
+                None => {
+                    Provenance {
+                        mech,
+                        iteration: 0,
+                        epoch: 0,
+                        parents: BTreeSet::new(),
+                        antecedents: BTreeMap::new(),
+                    }
+                }
+            };
+            data.insert(item.id, Rc::new(serde_json::from_value(item.data)?));
+            graph.insert(item.id,
+                         Item {
+                             mech,
+                             counts: item.counts,
+                             adj: item.adj,
+                             weights: item.weights,
+                             id: item.id,
+                             provenance,
+                         });
+        }
+        let mut epochs = Vec::with_capacity(self.epochs.len());
+        for epoch in self.epochs {
+            // the origin of an epoch may have since been retired
+            let mut present = epoch.context.iter().chain(epoch.accessed.iter());
+            if let Some(o) = present.find(|o| !ids.contains(o)) {
+                return Err(LoadError::Malformed(format!("epoch refers to missing {}", o)));
+            }
+            if epoch.origin >= next_id {
+                return Err(LoadError::Malformed(format!("epoch refers to missing {}", epoch.origin)));
+            }
+            epochs.push((epoch.origin, epoch.context, epoch.accessed));
+        }
+        Ok(Net {
+               context_min_size: self.context_min_size,
+               max_size: self.max_size,
+               graph,
+               data,
+               next_id,
+               epochs,
+               seed: self.seed,
+               rng: seeded_rng(self.seed, 0),
+               policy: Policy::default(),
+               observers: Observers::default(),
+               progress: BTreeMap::new(),
+           })
+    }
+}
+
+/// int_keys deserializes a map keyed by integers. JSON object keys are
+/// strings, and serde only converts them back to integers when the map is
+/// deserialized directly, not when it sits within a tagged journal event.
+pub(super) fn int_keys<'de, D, K, V>(d: D) -> Result<BTreeMap<K, V>, D::Error>
+    where D: Deserializer<'de>,
+          K: FromStr + Ord,
+          K::Err: fmt::Display,
+          V: Deserialize<'de>
+{
+    let raw = BTreeMap::<String, V>::deserialize(d)?;
+    raw.into_iter()
+        .map(|(k, v)| k.parse().map(|k| (k, v)).map_err(D::Error::custom))
+        .collect()
+}
+
+/// resolve finds the registered mechanism name matching a stored name.
+pub(super) fn resolve(mechs: &[&'static str], name: String) -> Result<&'static str, LoadError> {
+    match mechs.iter().find(|&&m| m == name) {
+        Some(&mech) => Ok(mech),
+        None => Err(LoadError::UnknownMechanism(name)),
+    }
+}
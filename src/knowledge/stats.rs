@@ -0,0 +1,162 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::collections::{BTreeSet, BTreeMap, VecDeque};
+
+/// Stats describes the shape of a knowledge network, for checking it
+/// against the scale-free assumption behind context sizes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Stats {
+    /// the number of items.
+    pub nodes: usize,
+    /// the number of edges.
+    pub edges: usize,
+    /// degrees maps a degree to the number of items with that degree.
+    pub degrees: BTreeMap<usize, usize>,
+    /// the largest degree of any item.
+    pub max_degree: usize,
+    /// the maximum-likelihood estimate of the power-law degree exponent
+    /// gamma, where P(k) ~ k^-gamma, if there is enough variation in
+    /// degree to fit one.
+    pub gamma: Option<f64>,
+    /// the average local clustering coefficient, where items with fewer
+    /// than two neighbors count as zero.
+    pub clustering: f64,
+    /// the number of connected components.
+    pub components: usize,
+    /// the longest shortest path between two connected items.
+    pub diameter: usize,
+    /// the mean length of shortest paths between connected items.
+    pub average_path_length: f64,
+}
+
+impl Stats {
+    /// of computes statistics for a graph given by the adjacency set of
+    /// each item.
+    pub(super) fn of<'a, I>(adj: I) -> Stats
+        where I: IntoIterator<Item = (usize, &'a BTreeSet<usize>)>
+    {
+        let adj: BTreeMap<usize, &BTreeSet<usize>> = adj.into_iter().collect();
+        let mut degrees = BTreeMap::new();
+        for a in adj.values() {
+            *degrees.entry(a.len()).or_insert(0) += 1;
+        }
+        let (components, diameter, average_path_length) = paths(&adj);
+        Stats {
+            nodes: adj.len(),
+            edges: adj.values().map(|a| a.len()).sum::<usize>() / 2,
+            max_degree: degrees.keys().next_back().cloned().unwrap_or(0),
+            gamma: power_law(&degrees),
+            clustering: clustering(&adj),
+            degrees,
+            components,
+            diameter,
+            average_path_length,
+        }
+    }
+}
+
+/// `power_law` fits the degree exponent by maximum likelihood, using the
+/// continuous approximation for discrete data with the smallest nonzero
+/// degree as the lower bound:
+///  gamma = 1 + n / sum_i ln(k_i / (k_min - 1/2))
+fn power_law(degrees: &BTreeMap<usize, usize>) -> Option<f64> {
+    let k_min = match degrees.keys().find(|&&k| k > 0) {
+        Some(&k) => k as f64 - 0.5,
+        None => return None,
+    };
+    let (n, sum) = degrees
+        .iter()
+        .filter(|&(&k, _)| k > 0)
+        .fold((0f64, 0f64), |(n, sum), (&k, &count)| {
+            (n + count as f64, sum + count as f64 * (k as f64 / k_min).ln())
+        });
+    if degrees.len() < 2 || sum <= 0f64 {
+        return None;
+    }
+    Some(1f64 + n / sum)
+}
+
+/// clustering averages, over every item, the proportion of pairs of its
+/// neighbors that are themselves adjacent.
+fn clustering(adj: &BTreeMap<usize, &BTreeSet<usize>>) -> f64 {
+    if adj.is_empty() {
+        return 0f64;
+    }
+    let total: f64 = adj.values()
+        .map(|a| {
+            let k = a.len();
+            if k < 2 {
+                return 0f64;
+            }
+            let links: usize = a.iter()
+                .map(|id| adj.get(id).map_or(0, |b| b.intersection(a).count()))
+                .sum();
+            links as f64 / (k * (k - 1)) as f64
+        })
+        .sum();
+    total / adj.len() as f64
+}
+
+/// paths finds the number of connected components, the diameter, and the
+/// average shortest path length, by breadth-first search from every item.
+fn paths(adj: &BTreeMap<usize, &BTreeSet<usize>>) -> (usize, usize, f64) {
+    let mut seen = BTreeSet::new();
+    let mut components = 0;
+    let mut diameter = 0;
+    let mut total = 0;
+    let mut pairs = 0;
+    for &source in adj.keys() {
+        if seen.insert(source) {
+            components += 1;
+        }
+        let mut dist = BTreeMap::new();
+        dist.insert(source, 0);
+        let mut queue = VecDeque::new();
+        queue.push_back(source);
+        while let Some(id) = queue.pop_front() {
+            let d = dist[&id];
+            for &oid in adj.get(&id).into_iter().flat_map(|a| a.iter()) {
+                if adj.contains_key(&oid) && !dist.contains_key(&oid) {
+                    dist.insert(oid, d + 1);
+                    seen.insert(oid);
+                    queue.push_back(oid);
+                }
+            }
+        }
+        for &d in dist.values().filter(|&&d| d > 0) {
+            diameter = diameter.max(d);
+            total += d;
+            pairs += 1;
+        }
+    }
+    let average = if pairs == 0 {
+        0f64
+    } else {
+        total as f64 / pairs as f64
+    };
+    (components, diameter, average)
+}
+
+/// Line is a single line of the per-iteration stats written by Skn::run.
+#[derive(Serialize)]
+pub(super) struct Line<'a> {
+    pub(super) iteration: u64,
+    #[serde(flatten)]
+    pub(super) stats: &'a Stats,
+}
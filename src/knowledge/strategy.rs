@@ -0,0 +1,510 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+use std::cmp::{min, max};
+use std::collections::{BTreeSet, BTreeMap};
+use std::fmt;
+
+use rand::{Rng, StdRng};
+use rand::distributions::{IndependentSample, Range};
+
+use super::Item;
+
+/// `NetView` is a read-only view of the knowledge network given to
+/// strategies.
+pub struct NetView<'a> {
+    pub(super) graph: &'a BTreeMap<usize, Item>,
+    pub(super) epochs: &'a [(usize, BTreeSet<usize>, BTreeSet<usize>)],
+    /// the immediate context of the mechanism's Context when growing, or
+    /// that of the latest epoch otherwise.
+    pub(super) context: &'a BTreeSet<usize>,
+    pub(super) context_min_size: usize,
+    pub(super) decay: Decay,
+}
+
+/// Decay is the model of how an item's accesses count towards its
+/// popularity as they age.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Decay {
+    /// popularity is the sum of accesses in every epoch since the epoch
+    /// that the mechanism's Context was created in.
+    #[default]
+    Window,
+    /// popularity is the sum of accesses in every epoch, each weighted by
+    /// one half for every this many epochs that have passed since.
+    HalfLife(f64),
+}
+
+impl Decay {
+    /// popularity weighs an item's access counts, where epoch begins the
+    /// lookback window and now is the latest epoch.
+    pub(super) fn popularity(&self, item: &Item, epoch: usize, now: usize) -> f64 {
+        match *self {
+            Decay::Window => item.recent_count(epoch) as f64,
+            Decay::HalfLife(half_life) => {
+                item.counts
+                    .iter()
+                    .map(|(&e, &c)| {
+                             let age = now.saturating_sub(e) as f64;
+                             (c as f64) * 0.5f64.powf(age / half_life)
+                         })
+                    .sum()
+            }
+        }
+    }
+}
+
+/// Ablation replaces the context that mechanisms get, to measure what
+/// choosing the context is worth. Only what `Context::get` gives is
+/// replaced: orienting, exploring and growing are as they would have been.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ablation {
+    /// the context is every item in the network.
+    Whole,
+    /// the context is every item of the embryo that remains.
+    Embryo,
+    /// the context is drawn uniformly at random from the network, with as
+    /// many items as the chosen context.
+    Random,
+}
+
+impl Ablation {
+    /// select gives the ids of the context that replaces one of the given
+    /// size.
+    pub(super) fn select(&self, graph: &BTreeMap<usize, Item>, size: usize, rng: &mut StdRng) -> BTreeSet<usize> {
+        match *self {
+            Ablation::Whole => graph.keys().cloned().collect(),
+            Ablation::Embryo => {
+                graph
+                    .values()
+                    .filter(|item| item.provenance.iteration == 0)
+                    .map(|item| item.id)
+                    .collect()
+            }
+            Ablation::Random => {
+                // a partial Fisher-Yates shuffle.
+                let mut ids: Vec<usize> = graph.keys().cloned().collect();
+                let n = ids.len();
+                for i in 0..min(size, n) {
+                    let j = rng.gen_range(i, n);
+                    ids.swap(i, j);
+                }
+                ids.into_iter().take(size).collect()
+            }
+        }
+    }
+}
+
+impl<'a> NetView<'a> {
+    /// len is the number of items in the network.
+    pub fn len(&self) -> usize {
+        self.graph.len()
+    }
+    /// `is_empty` is true if there are no items in the network.
+    pub fn is_empty(&self) -> bool {
+        self.graph.is_empty()
+    }
+    /// ids gives the ids of every item in the network, in order.
+    pub fn ids(&self) -> Vec<usize> {
+        self.graph.keys().cloned().collect()
+    }
+    /// neighbors gives the ids of items adjacent to the given item.
+    pub fn neighbors(&self, id: usize) -> &'a BTreeSet<usize> {
+        &self.graph[&id].adj
+    }
+    /// weight is the weight of the edge between two adjacent items, which
+    /// grows as they are accessed together.
+    pub fn weight(&self, id: usize, oid: usize) -> f64 {
+        self.graph[&id].weight(oid)
+    }
+    /// degree is the number of items adjacent to the given item.
+    pub fn degree(&self, id: usize) -> usize {
+        self.graph[&id].adj.len()
+    }
+    /// popularity weighs the accesses to the given item according to the
+    /// network's Decay, where the given epoch begins the lookback window.
+    pub fn popularity(&self, id: usize, epoch: usize) -> f64 {
+        let now = self.epochs.len() - 1;
+        self.decay.popularity(&self.graph[&id], epoch, now)
+    }
+    /// context gives the ids of the items in the immediate context of the
+    /// Context that is growing an item. Outside of growing, it is the
+    /// latest epoch's context.
+    pub fn context(&self) -> &'a BTreeSet<usize> {
+        self.context
+    }
+    /// `touched_since` gives the ids of items that were in a context or
+    /// accessed in any epoch since the given epoch.
+    pub fn touched_since(&self, epoch: usize) -> BTreeSet<usize> {
+        self.epochs
+            .iter()
+            .skip(epoch) // look for accesses as early as this epoch
+            .flat_map(|(_, cx, ru)| cx.union(ru).cloned())
+            .collect() // removes duplicates
+    }
+    /// `context_size` draws a context size according to the expected max
+    /// degree of a scale-free network with the current size n:
+    ///  k_max ~ n^{1/(gamma-1)}
+    /// for degree exponent 2 < gamma < 3. It is never less than the
+    /// network's minimum context size.
+    pub fn context_size(&self, rng: &mut StdRng) -> usize {
+        let n = self.len();
+        let exp = Range::new(0.5f64, 1f64).ind_sample(rng);
+        let prop = 0.5;
+        let kmax = prop * (n as f64).powf(exp);
+        max(self.context_min_size, min(n, kmax as usize))
+    }
+}
+
+/// `ContextStrategy` decides which items make up the context when a
+/// mechanism orients to an item.
+pub trait ContextStrategy: fmt::Debug {
+    /// select gives the ids of the context centered on origin, using
+    /// items' access counts since the given epoch. Only called when the
+    /// network is at least as large as its minimum context size.
+    fn select(&self, net: &NetView, origin: usize, epoch: usize, rng: &mut StdRng) -> BTreeSet<usize>;
+}
+
+/// Greedy starts with the origin, adds its neighbors, then repeats with
+/// the most popular of those neighbors (weighed by the edge it was reached
+/// by) until the context is full. This is the default strategy.
+#[derive(Debug, Default)]
+pub struct Greedy;
+
+impl ContextStrategy for Greedy {
+    fn select(&self, net: &NetView, origin: usize, epoch: usize, rng: &mut StdRng) -> BTreeSet<usize> {
+        let size = net.context_size(rng);
+
+        // start with the given node. add its neighbors in order by
+        // popularity, select most popular neighbor and repeat. stop
+        // when we get to `size`. heavier edges are preferred throughout.
+        let mut ctx = BTreeSet::new();
+        ctx.insert(origin);
+        let mut selected = origin;
+        while ctx.len() < size {
+            let mut ext: Vec<usize> = net.neighbors(selected).difference(&ctx).cloned().collect();
+            if ext.is_empty() {
+                break;
+            }
+            if ext.len() + ctx.len() > size {
+                // truncate less-used items
+                let take = size - ctx.len();
+                ext.sort_by(|&a, &b| {
+                                let (wa, wb) = (net.weight(selected, a), net.weight(selected, b));
+                                wb.partial_cmp(&wa)
+                                    .unwrap()
+                                    .then(net.degree(b).cmp(&net.degree(a)))
+                            });
+                ctx.extend(ext.iter().take(take));
+                break;
+            }
+            let score = |id| net.weight(selected, id) * (1f64 + net.popularity(id, epoch));
+            selected = *ext.iter()
+                .max_by(|&&a, &&b| score(a).partial_cmp(&score(b)).unwrap())
+                .unwrap();
+            ctx.extend(ext);
+        }
+        ctx
+    }
+}
+
+/// Ball takes every item within a number of hops of the origin, nearest
+/// first and most popular first within each hop, up to the drawn context
+/// size.
+#[derive(Debug)]
+pub struct Ball {
+    pub hops: usize,
+}
+
+impl ContextStrategy for Ball {
+    fn select(&self, net: &NetView, origin: usize, epoch: usize, rng: &mut StdRng) -> BTreeSet<usize> {
+        let size = net.context_size(rng);
+        let mut ctx = BTreeSet::new();
+        ctx.insert(origin);
+        let mut layer = vec![origin];
+        for _ in 0..self.hops {
+            let mut next: Vec<usize> = layer
+                .iter()
+                .flat_map(|&id| net.neighbors(id).iter().cloned())
+                .filter(|id| !ctx.contains(id))
+                .collect::<BTreeSet<usize>>()
+                .into_iter()
+                .collect();
+            next.sort_by(|&a, &b| {
+                             net.popularity(b, epoch)
+                                 .partial_cmp(&net.popularity(a, epoch))
+                                 .unwrap()
+                         });
+            next.truncate(size - ctx.len());
+            ctx.extend(next.iter().cloned());
+            if next.is_empty() || ctx.len() >= size {
+                break;
+            }
+            layer = next;
+        }
+        ctx
+    }
+}
+
+/// `PageRank` takes the items with the highest personalized PageRank with
+/// respect to the origin, up to the drawn context size.
+#[derive(Debug)]
+pub struct PageRank {
+    /// the probability of following an edge rather than teleporting back
+    /// to the origin.
+    pub damping: f64,
+    /// the number of power iterations.
+    pub iterations: usize,
+}
+
+impl Default for PageRank {
+    fn default() -> PageRank {
+        PageRank {
+            damping: 0.85,
+            iterations: 30,
+        }
+    }
+}
+
+impl ContextStrategy for PageRank {
+    fn select(&self, net: &NetView, origin: usize, _: usize, rng: &mut StdRng) -> BTreeSet<usize> {
+        let size = net.context_size(rng);
+        let mut rank: BTreeMap<usize, f64> = net.ids().into_iter().map(|id| (id, 0f64)).collect();
+        rank.insert(origin, 1f64);
+        for _ in 0..self.iterations {
+            let mut next: BTreeMap<usize, f64> = rank.keys().map(|&id| (id, 0f64)).collect();
+            *next.get_mut(&origin).unwrap() = 1f64 - self.damping;
+            for (&id, r) in &rank {
+                let adj = net.neighbors(id);
+                if adj.is_empty() {
+                    *next.get_mut(&origin).unwrap() += self.damping * r;
+                    continue;
+                }
+                let share = self.damping * r / (adj.len() as f64);
+                for o in adj {
+                    *next.get_mut(o).unwrap() += share;
+                }
+            }
+            rank = next;
+        }
+        let mut ranked: Vec<usize> = rank.iter()
+            .filter(|&(&id, &r)| id != origin && r > 0f64)
+            .map(|(&id, _)| id)
+            .collect();
+        ranked.sort_by(|a, b| rank[b].partial_cmp(&rank[a]).unwrap());
+        let mut ctx: BTreeSet<usize> = ranked.into_iter().take(size - 1).collect();
+        ctx.insert(origin);
+        ctx
+    }
+}
+
+/// `RandomWalk` takes the items visited by a random walk that restarts at
+/// the origin, until the drawn context size is reached or the walk runs
+/// out of steps.
+#[derive(Debug)]
+pub struct RandomWalk {
+    /// the probability of returning to the origin at each step.
+    pub restart: f64,
+    /// the maximum number of steps to walk.
+    pub steps: usize,
+}
+
+impl Default for RandomWalk {
+    fn default() -> RandomWalk {
+        RandomWalk {
+            restart: 0.3,
+            steps: 1000,
+        }
+    }
+}
+
+impl ContextStrategy for RandomWalk {
+    fn select(&self, net: &NetView, origin: usize, _: usize, rng: &mut StdRng) -> BTreeSet<usize> {
+        let size = net.context_size(rng);
+        let uniform = Range::new(0f64, 1.);
+        let mut ctx = BTreeSet::new();
+        ctx.insert(origin);
+        let mut at = origin;
+        for _ in 0..self.steps {
+            if ctx.len() >= size {
+                break;
+            }
+            let adj: Vec<usize> = net.neighbors(at).iter().cloned().collect();
+            if adj.is_empty() || uniform.ind_sample(rng) < self.restart {
+                at = origin;
+                continue;
+            }
+            at = *rng.choose(&adj).unwrap();
+            ctx.insert(at);
+        }
+        ctx
+    }
+}
+
+/// Attachment is the outcome of an `AttachmentStrategy`.
+#[derive(Debug)]
+pub struct Attachment {
+    /// the ids of the new item's neighbors. Must be non-empty unless the
+    /// network is, as when every item was retired to make room.
+    pub edges: BTreeSet<usize>,
+    /// the items that were considered as neighbors, with the probability
+    /// that each would be chosen.
+    pub antecedents: BTreeMap<usize, f64>,
+}
+
+/// `AttachmentStrategy` decides which existing items a newly grown item is
+/// adjacent to.
+pub trait AttachmentStrategy: fmt::Debug {
+    /// attach chooses the new item's neighbors, where epoch is the epoch
+    /// that the growing mechanism's Context was created in.
+    fn attach(&self, net: &NetView, epoch: usize, rng: &mut StdRng) -> Attachment;
+}
+
+/// Popularity considers every item touched since the epoch as an
+/// antecedent, connecting to each with probability proportional to one
+/// more than its popularity, and falls back to choosing a single
+/// antecedent by those probabilities (as in a CRP) if none were connected.
+/// This is the default strategy.
+#[derive(Debug, Default)]
+pub struct Popularity;
+
+impl AttachmentStrategy for Popularity {
+    fn attach(&self, net: &NetView, epoch: usize, rng: &mut StdRng) -> Attachment {
+        // compute counts for antecedent artifacts
+        let mut sum = 0f64;
+        let counts: Vec<(usize, f64)> = net.touched_since(epoch)
+            .into_iter()
+            .map(|id| {
+                     let count = 1f64 + net.popularity(id, epoch);
+                     sum += count;
+                     (id, count)
+                 })
+            .collect();
+
+        // convert counts to probabilities
+        let antecedents: BTreeMap<usize, f64> = counts
+            .iter()
+            .map(|&(id, cnt)| {
+                     let p = cnt / sum;
+                     (id, p)
+                 })
+            .collect();
+        let mut edges = BTreeSet::new();
+
+        // popularity-based subset selection
+        let uniform = Range::new(0f64, 1.);
+        for (id, p) in &antecedents {
+            let sample = uniform.ind_sample(rng);
+            let connect = sample <= *p;
+            if connect {
+                edges.insert(*id);
+            }
+        }
+        // CRP if we got an empty subset
+        if edges.is_empty() {
+            let mut r = uniform.ind_sample(rng);
+            for (id, p) in &antecedents {
+                r -= *p;
+                if r < 0f64 {
+                    edges.insert(*id);
+                    break;
+                }
+            }
+        }
+        Attachment { edges, antecedents }
+    }
+}
+
+/// Preferential is classic Barabási–Albert preferential attachment: the
+/// new item connects to m distinct items (at least one), each chosen with
+/// probability proportional to one more than its degree, so that items
+/// without neighbors can still be chosen.
+#[derive(Debug)]
+pub struct Preferential {
+    pub m: usize,
+}
+
+impl AttachmentStrategy for Preferential {
+    fn attach(&self, net: &NetView, _: usize, rng: &mut StdRng) -> Attachment {
+        let mut candidates: Vec<(usize, f64)> = net.ids()
+            .into_iter()
+            .map(|id| (id, 1f64 + net.degree(id) as f64))
+            .collect();
+        let total: f64 = candidates.iter().map(|&(_, w)| w).sum();
+        let m = min(max(1, self.m), candidates.len());
+        let antecedents = candidates
+            .iter()
+            .map(|&(id, w)| (id, (m as f64 * w / total).min(1f64)))
+            .collect();
+        let mut edges = BTreeSet::new();
+        let uniform = Range::new(0f64, 1.);
+        while edges.len() < m {
+            let sum: f64 = candidates.iter().map(|&(_, w)| w).sum();
+            let mut r = sum * uniform.ind_sample(rng);
+            let mut chosen = candidates.len() - 1;
+            for (i, &(_, w)) in candidates.iter().enumerate() {
+                r -= w;
+                if r < 0f64 {
+                    chosen = i;
+                    break;
+                }
+            }
+            edges.insert(candidates.remove(chosen).0);
+        }
+        Attachment { edges, antecedents }
+    }
+}
+
+/// Fixed connects the new item to m distinct items chosen uniformly at
+/// random.
+#[derive(Debug)]
+pub struct Fixed {
+    pub m: usize,
+}
+
+impl AttachmentStrategy for Fixed {
+    fn attach(&self, net: &NetView, _: usize, rng: &mut StdRng) -> Attachment {
+        let mut ids = net.ids();
+        if ids.is_empty() {
+            return Attachment {
+                       edges: BTreeSet::new(),
+                       antecedents: BTreeMap::new(),
+                   };
+        }
+        let m = min(max(1, self.m), ids.len());
+        let p = m as f64 / ids.len() as f64;
+        let antecedents = ids.iter().map(|&id| (id, p)).collect();
+        rng.shuffle(&mut ids);
+        let edges = ids.into_iter().take(m).collect();
+        Attachment { edges, antecedents }
+    }
+}
+
+/// `WholeContext` connects the new item to everything in the immediate
+/// context of the Context that grew it.
+#[derive(Debug, Default)]
+pub struct WholeContext;
+
+impl AttachmentStrategy for WholeContext {
+    fn attach(&self, net: &NetView, _: usize, _: &mut StdRng) -> Attachment {
+        let edges = net.context().clone();
+        let antecedents = edges.iter().map(|&id| (id, 1f64)).collect();
+        Attachment { edges, antecedents }
+    }
+}
@@ -15,7 +15,6 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-#[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
@@ -26,34 +25,495 @@ extern crate tempdir;
 
 pub mod knowledge;
 pub mod ec;
+/// plot is for drawing charts of the results of runs.
+pub mod plot;
+
+use std::fs::{File, OpenOptions};
+use std::time::Duration;
+use clap::{Arg, App, ErrorKind, SubCommand};
+use knowledge::{strategy, Eviction, OnFailure, Stop};
+use knowledge::strategy::Ablation;
 
-use std::fs::File;
-use clap::{Arg, App};
+/// the options that configure only the network of a plain run, which the
+/// subcommands that build their own networks reject rather than ignore.
+static RUN_ONLY: [&str; 22] = ["dot", "save", "load", "checkpoint", "resume", "journal", "context",
+                               "attach", "max-size", "evict", "half-life", "ablate", "stats",
+                               "metrics-tsv", "metrics-jsonl", "no-store-inputs", "localize",
+                               "no-growth", "plateau", "time-limit", "stop-size", "on-failure"];
+
+struct Args {
+    dot: Option<String>,
+    save: Option<String>,
+    load: Option<String>,
+    checkpoint: Option<String>,
+    resume: Option<String>,
+    journal: Option<String>,
+    seed: Option<u64>,
+    context: Option<String>,
+    attach: Option<String>,
+    max_size: Option<usize>,
+    evict: Option<String>,
+    half_life: Option<f64>,
+    ablate: Option<String>,
+    stats: Option<String>,
+    metrics_tsv: Option<String>,
+    metrics_jsonl: Option<String>,
+    no_store_inputs: bool,
+    localize: bool,
+    no_growth: Option<u64>,
+    plateau: Option<usize>,
+    time_limit: Option<u64>,
+    stop_size: Option<usize>,
+    on_failure: Option<String>,
+    limits: ec::Limits,
+    compare: Option<ec::Compare>,
+    plot: Option<plot::Figure>,
+    trials: Option<ec::Trials>,
+}
 
-fn argparse() -> Option<String> {
+fn argparse() -> Args {
     let matches = App::new("skn with ec")
         .arg(Arg::with_name("dot")
                  .long("dot")
                  .value_name("FILE")
                  .help("writes graphviz dot to file")
                  .takes_value(true))
+        .arg(Arg::with_name("save")
+                 .long("save")
+                 .value_name("FILE")
+                 .help("writes a snapshot of the final network to file")
+                 .takes_value(true))
+        .arg(Arg::with_name("load")
+                 .long("load")
+                 .value_name("FILE")
+                 .help("starts from the network in a snapshot file")
+                 .takes_value(true))
+        .arg(Arg::with_name("checkpoint")
+                 .long("checkpoint")
+                 .value_name("FILE")
+                 .help("writes a checkpoint to file after each iteration")
+                 .takes_value(true))
+        .arg(Arg::with_name("resume")
+                 .long("resume")
+                 .value_name("FILE")
+                 .help("resumes from a checkpoint file")
+                 .takes_value(true)
+                 .conflicts_with("load"))
+        .arg(Arg::with_name("seed")
+                 .long("seed")
+                 .value_name("N")
+                 .help("seeds the network for a reproducible run")
+                 .takes_value(true))
+        .arg(Arg::with_name("context")
+                 .long("context")
+                 .value_name("STRATEGY")
+                 .help("selects contexts by a strategy other than greedy")
+                 .possible_values(&["greedy", "ball", "pagerank", "walk"])
+                 .takes_value(true))
+        .arg(Arg::with_name("attach")
+                 .long("attach")
+                 .value_name("STRATEGY")
+                 .help("attaches new items by a strategy other than popularity")
+                 .possible_values(&["popularity", "preferential", "fixed", "context"])
+                 .takes_value(true))
+        .arg(Arg::with_name("max-size")
+                 .long("max-size")
+                 .value_name("N")
+                 .help("retires items once the network has this many")
+                 .takes_value(true))
+        .arg(Arg::with_name("evict")
+                 .long("evict")
+                 .value_name("POLICY")
+                 .help("retires items by a policy other than least-recently-accessed")
+                 .possible_values(&["lra", "count", "degree"])
+                 .takes_value(true))
+        .arg(Arg::with_name("half-life")
+                 .long("half-life")
+                 .value_name("EPOCHS")
+                 .help("decays item popularity with this half-life instead of windowing it")
+                 .validator(|s| match s.parse::<f64>() {
+                                    Ok(h) if h.is_finite() && h > 0f64 => Ok(()),
+                                    _ => Err(String::from("half-life must be a positive number of epochs")),
+                                })
+                 .takes_value(true))
+        .arg(Arg::with_name("ablate")
+                 .long("ablate")
+                 .value_name("CONTEXT")
+                 .help("gives mechanisms this instead of the chosen context, for comparison")
+                 .possible_values(&["whole", "embryo", "random"])
+                 .takes_value(true))
+        .arg(Arg::with_name("journal")
+                 .long("journal")
+                 .value_name("FILE")
+                 .help("appends every change to the network to file")
+                 .takes_value(true))
+        .arg(Arg::with_name("stats")
+                 .long("stats")
+                 .value_name("FILE")
+                 .help("writes statistics of the network to file after each iteration")
+                 .takes_value(true))
+        .arg(Arg::with_name("metrics-tsv")
+                 .long("metrics-tsv")
+                 .value_name("FILE")
+                 .help("writes a row for each task ec solves to file as tab-separated values")
+                 .takes_value(true))
+        .arg(Arg::with_name("metrics-jsonl")
+                 .long("metrics-jsonl")
+                 .value_name("FILE")
+                 .help("writes metrics of each ec iteration to file as JSON lines")
+                 .takes_value(true))
+        .arg(Arg::with_name("no-store-inputs")
+                 .long("no-store-inputs")
+                 .help("doesn't keep the input given to ec in each iteration"))
+        .arg(Arg::with_name("localize")
+                 .long("localize")
+                 .help("orients to the part of the network that suits each course before ec"))
+        .arg(Arg::with_name("no-growth")
+                 .long("no-growth")
+                 .value_name("N")
+                 .help("stops once nothing has been grown for this many iterations")
+                 .takes_value(true))
+        .arg(Arg::with_name("plateau")
+                 .long("plateau")
+                 .value_name("N")
+                 .help("stops once ec's hit rate hasn't improved for this many iterations")
+                 .takes_value(true))
+        .arg(Arg::with_name("time-limit")
+                 .long("time-limit")
+                 .value_name("SECONDS")
+                 .help("stops after the iteration that exceeds this much time")
+                 .takes_value(true))
+        .arg(Arg::with_name("stop-size")
+                 .long("stop-size")
+                 .value_name("N")
+                 .help("stops once the network has this many items")
+                 .takes_value(true))
+        .arg(Arg::with_name("on-failure")
+                 .long("on-failure")
+                 .value_name("POLICY")
+                 .help("what to do when ec fails in an iteration, after any --ec-retries: log \
+                        it and carry on (the default), or skip the rest of the iteration")
+                 .possible_values(&["log", "skip"])
+                 .takes_value(true))
+        .arg(Arg::with_name("ec-timeout")
+                 .long("ec-timeout")
+                 .value_name("SECONDS")
+                 .help("kills ec and fails its iteration once it has run this long")
+                 .takes_value(true))
+        .arg(Arg::with_name("ec-retries")
+                 .long("ec-retries")
+                 .value_name("N")
+                 .help("the number of times to run ec again when it fails or times out")
+                 .default_value("0"))
+        .arg(Arg::with_name("ec-backoff")
+                 .long("ec-backoff")
+                 .value_name("SECONDS")
+                 .help("the wait before running ec again, doubled with each retry")
+                 .default_value("1"))
+        .arg(Arg::with_name("ec-cpu")
+                 .long("ec-cpu")
+                 .value_name("SECONDS")
+                 .help("limits the CPU time ec may use (Linux only)")
+                 .takes_value(true))
+        .arg(Arg::with_name("ec-memory")
+                 .long("ec-memory")
+                 .value_name("MB")
+                 .help("limits the memory ec may use (Linux only)")
+                 .takes_value(true))
+        .subcommand(SubCommand::with_name("compare")
+                        .about("compares contextual learning against baselines")
+                        .arg(Arg::with_name("curriculum")
+                                 .long("curriculum")
+                                 .value_name("DIR")
+                                 .help("the curriculum to learn from")
+                                 .default_value("curriculum/ec_reiter"))
+                        .arg(Arg::with_name("all")
+                                 .long("all")
+                                 .value_name("FILE")
+                                 .help("a course with every task in the domain")
+                                 .default_value("curriculum/all.json"))
+                        .arg(Arg::with_name("phases")
+                                 .long("phases")
+                                 .value_name("N")
+                                 .help("the number of phases to measure: the first for the per-phase baselines, \
+                                        and the last for the contextual run")
+                                 .default_value("3"))
+                        .arg(Arg::with_name("out")
+                                 .long("out")
+                                 .value_name("DIR")
+                                 .help("writes results to this directory")
+                                 .default_value("compare"))
+                        .arg(Arg::with_name("ablations")
+                                 .long("ablations")
+                                 .help("also runs the contextual condition with each --ablate")))
+        .subcommand(SubCommand::with_name("trials")
+                        .about("runs the curriculum many times with distinct seeds and summarizes")
+                        .arg(Arg::with_name("trials")
+                                 .long("trials")
+                                 .value_name("N")
+                                 .help("the number of trials, seeded from --seed onward")
+                                 .default_value("10"))
+                        .arg(Arg::with_name("out")
+                                 .long("out")
+                                 .value_name("DIR")
+                                 .help("writes each trial and a summary to this directory")
+                                 .default_value("trials")))
+        .subcommand(SubCommand::with_name("plot")
+                        .about("draws a chart of results from TSVs as SVG")
+                        .arg(Arg::with_name("type")
+                                 .help("the type of chart")
+                                 .possible_values(&["speed_total", "speed_iter", "likelihood"])
+                                 .required(true))
+                        .arg(Arg::with_name("tsv")
+                                 .help("TSVs of results, such as those written by compare")
+                                 .multiple(true)
+                                 .required(true))
+                        .arg(Arg::with_name("out")
+                                 .long("out")
+                                 .value_name("FILE")
+                                 .help("writes the chart to file instead of <type>.svg")
+                                 .takes_value(true)))
         .get_matches();
-    match matches.value_of("dot") {
-        Some(s) => Some(String::from(s)),
-        _ => None,
+    if let Some(sub) = matches.subcommand_name().filter(|&sub| sub == "compare" || sub == "trials") {
+        if let Some(arg) = RUN_ONLY.iter().find(|&&arg| matches.is_present(arg)) {
+            clap::Error::with_description(&format!("--{} can't be used with {}", arg, sub),
+                                          ErrorKind::ArgumentConflict)
+                .exit();
+        }
+    }
+    let seed = matches
+        .value_of("seed")
+        .map(|s| s.parse().expect("seed must be a non-negative integer"));
+    let limits = ec::Limits {
+        timeout: matches
+            .value_of("ec-timeout")
+            .map(|s| Duration::from_secs(s.parse().expect("ec timeout must be a whole number of seconds"))),
+        retries: matches
+            .value_of("ec-retries")
+            .unwrap()
+            .parse()
+            .expect("ec retries must be a non-negative integer"),
+        backoff: Duration::from_secs(matches
+                                         .value_of("ec-backoff")
+                                         .unwrap()
+                                         .parse()
+                                         .expect("ec backoff must be a whole number of seconds")),
+        cpu_seconds: matches
+            .value_of("ec-cpu")
+            .map(|s| s.parse().expect("ec cpu limit must be a whole number of seconds")),
+        memory_bytes: matches
+            .value_of("ec-memory")
+            .map(|s| {
+                     let mb: u64 = s.parse().expect("ec memory limit must be a whole number of megabytes");
+                     mb << 20
+                 }),
+    };
+    Args {
+        dot: matches.value_of("dot").map(String::from),
+        save: matches.value_of("save").map(String::from),
+        load: matches.value_of("load").map(String::from),
+        checkpoint: matches.value_of("checkpoint").map(String::from),
+        resume: matches.value_of("resume").map(String::from),
+        journal: matches.value_of("journal").map(String::from),
+        seed,
+        context: matches.value_of("context").map(String::from),
+        attach: matches.value_of("attach").map(String::from),
+        max_size: matches
+            .value_of("max-size")
+            .map(|s| s.parse().expect("max size must be a positive integer")),
+        evict: matches.value_of("evict").map(String::from),
+        ablate: matches.value_of("ablate").map(String::from),
+        half_life: matches
+            .value_of("half-life")
+            .map(|s| s.parse().expect("half-life must be a number")),
+        stats: matches.value_of("stats").map(String::from),
+        metrics_tsv: matches.value_of("metrics-tsv").map(String::from),
+        metrics_jsonl: matches.value_of("metrics-jsonl").map(String::from),
+        no_store_inputs: matches.is_present("no-store-inputs"),
+        localize: matches.is_present("localize"),
+        no_growth: matches
+            .value_of("no-growth")
+            .map(|s| s.parse().expect("no-growth must be a positive integer")),
+        plateau: matches
+            .value_of("plateau")
+            .map(|s| s.parse().expect("plateau must be a positive integer")),
+        time_limit: matches
+            .value_of("time-limit")
+            .map(|s| s.parse().expect("time limit must be a whole number of seconds")),
+        stop_size: matches
+            .value_of("stop-size")
+            .map(|s| s.parse().expect("stop size must be a positive integer")),
+        on_failure: matches.value_of("on-failure").map(String::from),
+        compare: matches
+            .subcommand_matches("compare")
+            .map(|m| {
+                     ec::Compare {
+                         curriculum: String::from(m.value_of("curriculum").unwrap()),
+                         ec: ec::ec_bin(),
+                         ec_one: ec::ec_one_bin(),
+                         all: String::from(m.value_of("all").unwrap()),
+                         phases: m.value_of("phases")
+                             .unwrap()
+                             .parse()
+                             .expect("phases must be a positive integer"),
+                         out: String::from(m.value_of("out").unwrap()),
+                         seed,
+                         ablations: m.is_present("ablations"),
+                         limits: limits.clone(),
+                     }
+                 }),
+        trials: matches
+            .subcommand_matches("trials")
+            .map(|m| {
+                     ec::Trials {
+                         curriculum: ec::default_curriculum().expect("find curriculum"),
+                         ec: ec::ec_bin(),
+                         trials: m.value_of("trials")
+                             .unwrap()
+                             .parse()
+                             .expect("trials must be a positive integer"),
+                         seed,
+                         out: String::from(m.value_of("out").unwrap()),
+                         limits: limits.clone(),
+                     }
+                 }),
+        plot: matches
+            .subcommand_matches("plot")
+            .map(|m| {
+                     let plot = plot::Plot::from_name(m.value_of("type").unwrap()).unwrap();
+                     plot::Figure {
+                         plot,
+                         tsvs: m.values_of("tsv").unwrap().map(String::from).collect(),
+                         out: m.value_of("out")
+                             .map_or_else(|| format!("{}.svg", plot.name()), String::from),
+                     }
+                 }),
+        limits,
     }
 }
 
 fn main() {
-    let dot = argparse();
+    let args = argparse();
+    if let Some(compare) = args.compare {
+        compare.run().expect("compare");
+        return;
+    }
+    if let Some(trials) = args.trials {
+        trials.run().expect("run trials");
+        return;
+    }
+    if let Some(figure) = args.plot {
+        figure.run();
+        return;
+    }
 
-    let t = ec::iter_max();
-    let embryo = ec::embryo();
-    let mech = ec::mech;
-    let mut skn = knowledge::Skn::new(embryo, t);
-    skn.register("ec", &mech);
-    skn.run();
-    if let Some(path) = dot {
+    let curriculum = ec::default_curriculum().expect("find curriculum");
+    let t = ec::iter_max(&curriculum).expect("find curriculum");
+    let embryo = ec::embryo(&curriculum).expect("load embryo");
+    let mut skn = match args.seed {
+        Some(seed) => knowledge::Skn::with_seed(embryo, t, seed),
+        None => knowledge::Skn::new(embryo, t),
+    };
+    let mut ec = ec::Ec::new();
+    ec.use_curriculum(&curriculum);
+    ec.store_inputs(!args.no_store_inputs);
+    ec.set_limits(args.limits);
+    if let Some(path) = args.metrics_tsv {
+        let f = File::create(path).expect("create metrics tsv file");
+        ec.metrics_tsv_to(f);
+    }
+    if let Some(path) = args.metrics_jsonl {
+        let f = File::create(path).expect("create metrics jsonl file");
+        ec.metrics_jsonl_to(f);
+    }
+    skn.register("ec", ec);
+    if args.localize {
+        let first = knowledge::Schedule {
+            priority: 1,
+            ..knowledge::Schedule::default()
+        };
+        let mut localizer = ec::Localizer::new();
+        localizer.use_curriculum(&curriculum);
+        skn.register_with_schedule("localize", localizer, first);
+    }
+    match args.context.as_deref() {
+        Some("ball") => skn.set_context_strategy(strategy::Ball { hops: 2 }),
+        Some("pagerank") => skn.set_context_strategy(strategy::PageRank::default()),
+        Some("walk") => skn.set_context_strategy(strategy::RandomWalk::default()),
+        _ => (),
+    }
+    match args.attach.as_deref() {
+        Some("preferential") => skn.set_attachment_strategy(strategy::Preferential { m: 2 }),
+        Some("fixed") => skn.set_attachment_strategy(strategy::Fixed { m: 2 }),
+        Some("context") => skn.set_attachment_strategy(strategy::WholeContext),
+        _ => (),
+    }
+    if let Some(half_life) = args.half_life {
+        skn.set_decay(strategy::Decay::HalfLife(half_life));
+    }
+    if let Some(max_size) = args.max_size {
+        skn.set_max_size(max_size);
+    }
+    match args.ablate.as_deref() {
+        Some("whole") => skn.set_ablation(Ablation::Whole),
+        Some("embryo") => skn.set_ablation(Ablation::Embryo),
+        Some("random") => skn.set_ablation(Ablation::Random),
+        _ => (),
+    }
+    match args.evict.as_deref() {
+        Some("count") => skn.set_eviction(Eviction::LowestCount),
+        Some("degree") => skn.set_eviction(Eviction::LowestDegree),
+        _ => (),
+    }
+    if let Some(path) = args.load {
+        let f = File::open(path).expect("open snapshot file");
+        skn.load(f).expect("load snapshot");
+    }
+    if let Some(path) = args.resume {
+        skn.resume_from(path).expect("resume from checkpoint");
+    }
+    if let Some(path) = args.checkpoint {
+        skn.checkpoint_to(path);
+    }
+    if let Some(path) = args.journal {
+        let f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("open journal file");
+        skn.journal_to(f);
+    }
+    if let Some(path) = args.stats {
+        let f = File::create(path).expect("create stats file");
+        skn.stats_to(f);
+    }
+    if args.on_failure.as_deref() == Some("skip") {
+        skn.on_failure(OnFailure::Skip);
+    }
+    if let Some(k) = args.no_growth {
+        skn.stop_when(Stop::NoGrowth(k));
+    }
+    if let Some(window) = args.plateau {
+        skn.stop_when(Stop::Plateau {
+                          mech: "ec",
+                          window,
+                          tolerance: 0f64,
+                      });
+    }
+    if let Some(secs) = args.time_limit {
+        skn.stop_when(Stop::WallClock(Duration::from_secs(secs)));
+    }
+    if let Some(n) = args.stop_size {
+        skn.stop_when(Stop::Size(n));
+    }
+    if let Some((t, stop)) = skn.run() {
+        eprintln!("stopped after iteration {}: {:?}", t, stop);
+    }
+    if let Some(path) = args.save {
+        let mut f = File::create(path).expect("create snapshot file");
+        skn.save(&mut f).expect("write snapshot");
+    }
+    if let Some(path) = args.dot {
         let mut f = File::create(path).expect("create dot file");
         skn.dot(&mut f).unwrap();
     }
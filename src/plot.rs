@@ -0,0 +1,470 @@
+// This file is part of Context, a program and library for machine learning.
+// This program is available at <https://docs.lucasem.com/context_src>
+// Copyright (C) 2017  Lucas E. Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// the names of the conditions of a comparison, by the name of the TSV
+/// that `context compare` writes for each.
+static NAMES: [(&str, &str); 7] = [("primitive", "primitive grammar"),
+                                   ("specialized_per_phase", "specialized grammar per-phase"),
+                                   ("specialized_full_domain", "specialized grammar full-domain"),
+                                   ("contextual", "contextual grammar"),
+                                   ("contextual_whole", "whole-network grammar"),
+                                   ("contextual_embryo", "embryo grammar"),
+                                   ("contextual_random", "random-context grammar")];
+
+/// the log probability that a failed task is drawn at, and that lower log
+/// probabilities are clamped to.
+const NEG_INF: f64 = -10f64;
+
+/// the names of the tasks in curriculum/all.json, in order. A task is
+/// labelled with its position here.
+static TASK_NAMES: [&str; 20] = ["nospaces",
+                                 "first letters",
+                                 "concat first letters",
+                                 "filter cap",
+                                 "SICP",
+                                 "ruby -> r",
+                                 "find dot",
+                                 "find less-than",
+                                 "discard after dot",
+                                 "drop first two chars",
+                                 "discard before less-than",
+                                 "discard after greater-than",
+                                 "#include<os.h> -> os.h",
+                                 "#include<os.h> -> os",
+                                 "#include<os.h> -> OS",
+                                 "discard after @",
+                                 "dots to spaces",
+                                 "feach cap",
+                                 "ruby.clinton -> Ruby Clinton",
+                                 "ruby.clinton@mit.edu -> Ruby Clinton"];
+
+/// the colors of each TSV's points, in order.
+static COLORS: [&str; 10] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+                             "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf"];
+
+const WIDTH: f64 = 400f64;
+const HEIGHT: f64 = 580f64;
+const LEFT: f64 = 50f64;
+const RIGHT: f64 = 15f64;
+const TOP: f64 = 30f64;
+const BOTTOM: f64 = 45f64;
+
+/// Plot is a type of chart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Plot {
+    /// how quickly each phase containing a task was completed.
+    SpeedTotal,
+    /// how quickly each task was solved in its final iteration.
+    SpeedIter,
+    /// the log likelihood of each task's solution.
+    Likelihood,
+}
+
+impl Plot {
+    /// `from_name` gives the Plot with a name like that of `Plot::name`.
+    pub fn from_name(name: &str) -> Option<Plot> {
+        match name.to_lowercase().as_str() {
+            "speed_total" => Some(Plot::SpeedTotal),
+            "speed_iter" => Some(Plot::SpeedIter),
+            "likelihood" => Some(Plot::Likelihood),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Plot::SpeedTotal => "speed_total",
+            Plot::SpeedIter => "speed_iter",
+            Plot::Likelihood => "likelihood",
+        }
+    }
+}
+
+/// Row is what a TSV gives for a task: the time ec took to solve it, the
+/// log probability of its solution, and the time taken by the whole ec run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Row {
+    pub time: f64,
+    pub log_probability: f64,
+    pub total: f64,
+}
+
+/// Table maps a task to its Row. Where a TSV has many rows for a task, the
+/// last is kept.
+pub type Table = BTreeMap<String, Row>;
+
+/// `read_table` parses a TSV with the columns task, time, log probability,
+/// and total time, such as is written by `context compare`. Any further
+/// columns are ignored. The total time is taken as given: it is wall-clock
+/// seconds in TSVs written by `context`, but was user CPU time in those of
+/// the former `results/produce_data.sh`.
+pub fn read_table<R: BufRead>(r: R) -> io::Result<Table> {
+    let mut table = Table::new();
+    for line in r.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let cols: Vec<&str> = line.split('\t').collect();
+        if cols.len() < 4 {
+            return Err(invalid(format!("expected at least 4 columns: {:?}", line)));
+        }
+        let num = |s: &str| {
+            s.parse::<f64>().map_err(|err| invalid(format!("{}: {:?}", err, s)))
+        };
+        table.insert(String::from(cols[0]),
+                     Row {
+                         time: num(cols[1])?,
+                         log_probability: num(cols[2])?,
+                         total: num(cols[3])?,
+                     });
+    }
+    Ok(table)
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Figure is a chart of the results of several runs, each from a TSV.
+#[derive(Clone, Debug)]
+pub struct Figure {
+    pub plot: Plot,
+    pub tsvs: Vec<String>,
+    /// the file the SVG is written to.
+    pub out: String,
+}
+
+impl Figure {
+    /// run reads the TSVs and writes the chart.
+    pub fn run(&self) {
+        let tables: Vec<(String, Table)> = self.tsvs
+            .iter()
+            .map(|path| {
+                     let f = File::open(path).expect("open tsv file");
+                     let table = read_table(BufReader::new(f)).expect("read tsv file");
+                     (label(path), table)
+                 })
+            .collect();
+        let mut f = File::create(&self.out).expect("create svg file");
+        svg(&mut f, self.plot, &tables).expect("write svg file");
+    }
+}
+
+/// label names a TSV in the legend, by its condition if it is one written
+/// by `context compare` or else by its file name.
+fn label(path: &str) -> String {
+    let stem = Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(path);
+    NAMES
+        .iter()
+        .find(|&&(name, _)| name == stem)
+        .map_or_else(|| String::from(stem), |&(_, label)| String::from(label))
+}
+
+/// Chart is what gets drawn: every task, and the value that each table
+/// gives it.
+struct Chart {
+    title: &'static str,
+    xlabel: &'static str,
+    /// fixed ticks on the x axis, with their labels.
+    xticks: Option<Vec<(f64, String)>>,
+    names: Vec<String>,
+    /// values has, for each table, a value for each name.
+    values: Vec<Vec<f64>>,
+}
+
+fn chart(plot: Plot, tables: &[&Table]) -> Chart {
+    let speed = |x: f64| 1f64 / x;
+    match plot {
+        Plot::SpeedTotal => {
+            let (names, mut values) = ordered(tables, |r| r.total, 0f64, speed);
+            scatterify(&mut values, 0.05);
+            Chart {
+                title: "phase completion speed",
+                xlabel: "solve speed (s⁻¹)",
+                xticks: None,
+                names,
+                values,
+            }
+        }
+        Plot::SpeedIter => {
+            let (names, mut values) = ordered(tables, |r| r.time, 0f64, speed);
+            scatterify(&mut values, 3f64);
+            Chart {
+                title: "final iteration task solve speed",
+                xlabel: "solve speed (s⁻¹)",
+                xticks: None,
+                names,
+                values,
+            }
+        }
+        Plot::Likelihood => {
+            let (names, mut values) =
+                ordered(tables, |r| r.log_probability, NEG_INF, |x| x.max(NEG_INF));
+            scatterify(&mut values, 0.2);
+            let xticks = (0..)
+                .map(|i| NEG_INF + 2f64 * i as f64)
+                .take_while(|&x| x <= 0f64)
+                .map(|x| {
+                         let label = if x == NEG_INF { String::from("-∞") } else { format!("{}", x) };
+                         (x, label)
+                     })
+                .collect();
+            Chart {
+                title: "log likelihood of solution",
+                xlabel: "log likelihood",
+                xticks: Some(xticks),
+                names,
+                values,
+            }
+        }
+    }
+}
+
+/// ordered gives every task in any table, and the value each table gives
+/// it. Tasks missing from a table get the default. Tasks are sorted by
+/// value, largest first, in the last table, with ties broken by the
+/// preceding tables.
+fn ordered<C, M>(tables: &[&Table], col: C, default: f64, mapping: M) -> (Vec<String>, Vec<Vec<f64>>)
+    where C: Fn(&Row) -> f64,
+          M: Fn(f64) -> f64
+{
+    let value = |table: &Table, name: &str| table.get(name).map_or(default, |r| mapping(col(r)));
+    let mut names: Vec<String> = tables.iter().flat_map(|t| t.keys().cloned()).collect();
+    names.sort();
+    names.dedup();
+    for table in tables {
+        // stable, so ties keep the order of the previous table.
+        names.sort_by(|a, b| {
+            value(table, b).partial_cmp(&value(table, a)).unwrap_or(Ordering::Equal)
+        });
+    }
+    let values = tables
+        .iter()
+        .map(|table| names.iter().map(|name| value(table, name)).collect())
+        .collect();
+    (names, values)
+}
+
+/// scatterify nudges apart the values that tables give the same task when
+/// they are within err of each other, so that points don't overlap.
+fn scatterify(values: &mut [Vec<f64>], err: f64) {
+    let n = values.first().map_or(0, |v| v.len());
+    for i in 0..n {
+        // bounded, as nudging a pair apart can push one into another.
+        for _ in 0..100 {
+            let close = (0..values.len())
+                .flat_map(|a| (a + 1..values.len()).map(move |b| (a, b)))
+                .find(|&(a, b)| (values[a][i] - values[b][i]).abs() < err);
+            let (a, b) = match close {
+                Some(pair) => pair,
+                None => break,
+            };
+            let (lo, hi) = if values[a][i] <= values[b][i] { (a, b) } else { (b, a) };
+            let mid = (values[lo][i] + values[hi][i]) / 2f64;
+            values[lo][i] = mid - err * 0.55;
+            values[hi][i] = mid + err * 0.55;
+        }
+    }
+}
+
+/// ticks gives evenly spaced round numbers spanning lo to hi.
+fn ticks(lo: f64, hi: f64) -> Vec<(f64, String)> {
+    let raw = (hi - lo) / 5f64;
+    let mag = 10f64.powf(raw.log10().floor());
+    let step = [1f64, 2f64, 5f64, 10f64]
+        .iter()
+        .map(|m| m * mag)
+        .find(|&s| s >= raw)
+        .unwrap_or(10f64 * mag);
+    let decimals = (-step.log10().floor()).max(0f64) as usize;
+    let first = (lo / step).ceil();
+    (0..)
+        .map(|k| (first + k as f64) * step)
+        .take_while(|&x| x <= hi + step * 1e-9)
+        .map(|x| if x.abs() < step * 1e-9 { 0f64 } else { x })
+        .map(|x| (x, format!("{:.*}", decimals, x)))
+        .collect()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// svg draws a chart of the given labelled tables as a standalone SVG.
+pub fn svg<W: Write>(w: &mut W, plot: Plot, tables: &[(String, Table)]) -> io::Result<()> {
+    let refs: Vec<&Table> = tables.iter().map(|(_, t)| t).collect();
+    let chart = chart(plot, &refs);
+    let n = chart.names.len();
+
+    // the extent of the axes, padded like matplotlib's.
+    let all = chart.values
+        .iter()
+        .flat_map(|v| v.iter().cloned())
+        .filter(|x| x.is_finite());
+    let (mut xmin, mut xmax) = all.fold((f64::INFINITY, f64::NEG_INFINITY),
+                                        |(lo, hi), x| (lo.min(x), hi.max(x)));
+    if let Some(ref xticks) = chart.xticks {
+        for &(x, _) in xticks {
+            xmin = xmin.min(x);
+            xmax = xmax.max(x);
+        }
+    }
+    if !xmin.is_finite() || !xmax.is_finite() {
+        xmin = 0f64;
+        xmax = 1f64;
+    }
+    if xmax - xmin < 1e-9 {
+        xmin -= 1f64;
+        xmax += 1f64;
+    }
+    let pad = (xmax - xmin) * 0.05;
+    let (xmin, xmax) = (xmin - pad, xmax + pad);
+    let (ymin, ymax) = (-1f64, n as f64 + 5f64);
+    let px = |x: f64| LEFT + (x - xmin) / (xmax - xmin) * (WIDTH - LEFT - RIGHT);
+    let py = |y: f64| HEIGHT - BOTTOM - (y - ymin) / (ymax - ymin) * (HEIGHT - TOP - BOTTOM);
+
+    writeln!(w,
+             r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" font-family="sans-serif" font-size="10">"#,
+             WIDTH,
+             HEIGHT,
+             WIDTH,
+             HEIGHT)?;
+    writeln!(w, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+    writeln!(w,
+             r#"<text x="{}" y="{}" text-anchor="middle" font-size="12">{}</text>"#,
+             (LEFT + WIDTH - RIGHT) / 2f64,
+             TOP - 10f64,
+             escape(chart.title))?;
+
+    // a line and label for each task.
+    for (i, name) in chart.names.iter().enumerate() {
+        let y = py(i as f64);
+        writeln!(w,
+                 r#"<line x1="{}" y1="{:.2}" x2="{}" y2="{:.2}" stroke="lightgrey"/>"#,
+                 LEFT,
+                 y,
+                 WIDTH - RIGHT,
+                 y)?;
+        let label = TASK_NAMES
+            .iter()
+            .position(|&t| t == name)
+            .map_or_else(|| name.clone(), |p| format!("{}", p + 1));
+        writeln!(w,
+                 r#"<text x="{}" y="{:.2}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+                 LEFT - 4f64,
+                 y,
+                 escape(&label))?;
+    }
+
+    // the x axis.
+    let xticks = chart.xticks.clone().unwrap_or_else(|| ticks(xmin, xmax));
+    for (x, label) in xticks {
+        let x = px(x);
+        writeln!(w,
+                 r#"<line x1="{:.2}" y1="{}" x2="{:.2}" y2="{}" stroke="black"/>"#,
+                 x,
+                 HEIGHT - BOTTOM,
+                 x,
+                 HEIGHT - BOTTOM + 4f64)?;
+        writeln!(w,
+                 r#"<text x="{:.2}" y="{}" text-anchor="middle">{}</text>"#,
+                 x,
+                 HEIGHT - BOTTOM + 15f64,
+                 escape(&label))?;
+    }
+    writeln!(w,
+             r#"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="black"/>"#,
+             LEFT,
+             TOP,
+             WIDTH - LEFT - RIGHT,
+             HEIGHT - TOP - BOTTOM)?;
+    writeln!(w,
+             r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+             (LEFT + WIDTH - RIGHT) / 2f64,
+             HEIGHT - 8f64,
+             escape(chart.xlabel))?;
+    writeln!(w,
+             r#"<text x="12" y="{}" text-anchor="middle" transform="rotate(-90 12 {})">task</text>"#,
+             (TOP + HEIGHT - BOTTOM) / 2f64,
+             (TOP + HEIGHT - BOTTOM) / 2f64)?;
+
+    // the points and legend of each table.
+    for (it, values) in chart.values.iter().enumerate() {
+        let color = COLORS[it % COLORS.len()];
+        for (i, &x) in values.iter().enumerate().filter(|&(_, x)| x.is_finite()) {
+            writeln!(w,
+                     r#"<circle cx="{:.2}" cy="{:.2}" r="3.5" fill="{}"/>"#,
+                     px(x),
+                     py(i as f64),
+                     color)?;
+        }
+        let y = TOP + 12f64 + 14f64 * it as f64;
+        let x = WIDTH - RIGHT - 170f64;
+        writeln!(w,
+                 r#"<circle cx="{}" cy="{}" r="3.5" fill="{}"/>"#,
+                 x,
+                 y,
+                 color)?;
+        writeln!(w,
+                 r#"<text x="{}" y="{}" dominant-baseline="middle">{}</text>"#,
+                 x + 8f64,
+                 y,
+                 escape(&tables[it].0))?;
+    }
+    writeln!(w, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Plot, read_table, svg};
+
+    #[test]
+    fn plot_tsvs() {
+        let primitive = "nospaces\t0.5\t-2.5\t4\t(B upper)\n\
+                         SICP\t1\t-12\t4\tK\n";
+        let contextual = "nospaces\t0.25\t-1\t2\t(B upper)\t1\n\
+                          filter cap\t2\t-3\t2\tcap\t1\n";
+        let tables = vec![(String::from("primitive"), read_table(primitive.as_bytes()).unwrap()),
+                          (String::from("contextual"), read_table(contextual.as_bytes()).unwrap())];
+        assert_eq!(tables[0].1["SICP"].log_probability, -12f64);
+        assert_eq!(tables[1].1.len(), 2);
+        assert!(read_table("nospaces\t0.5\n".as_bytes()).is_err());
+
+        let mut out = Vec::new();
+        svg(&mut out, Plot::Likelihood, &tables).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("<svg"));
+        assert!(out.trim_end().ends_with("</svg>"));
+        // a point per table per task, and one in the legend per table.
+        assert_eq!(out.matches("<circle").count(), 2 * 3 + 2);
+        assert!(out.contains(">-∞<"));
+        assert!(out.contains(">contextual<"));
+        assert_eq!(Plot::from_name("SPEED_ITER"), Some(Plot::SpeedIter));
+    }
+}